@@ -7,8 +7,16 @@ use std::cell::RefCell;
 
 use ashpd::WindowIdentifier;
 
-use crate::apps::{self, AppDetails};
-use crate::util;
+use crate::apps::{self, AppDetails, ProxyMode};
+use crate::clear_data_dialog::ClearDataDialog;
+use crate::content_filter;
+use crate::icon_editor_dialog::IconEditorDialog;
+use crate::injection_dialog::InjectionDialog;
+use crate::injections::{self, Injection};
+use crate::permissions::Permission;
+use crate::profiles::{self, Profile, DEFAULT_PROFILE};
+use crate::scope;
+use crate::util::{self, IconStyle};
 
 fn menu_item_and_target(label: &str, action_name: &str, action_target: &str) -> gio::MenuItem {
     let item = gio::MenuItem::new(Some(label), None);
@@ -16,6 +24,23 @@ fn menu_item_and_target(label: &str, action_name: &str, action_target: &str) ->
     item
 }
 
+// Indices of the choices in the permission combo rows
+fn permission_to_index(decision: Option<bool>) -> u32 {
+    match decision {
+        None => 0,
+        Some(true) => 1,
+        Some(false) => 2,
+    }
+}
+
+fn index_to_permission(index: u32) -> Option<bool> {
+    match index {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum DiffSignificance {
     // No difference between app details
@@ -40,10 +65,19 @@ mod imp {
         unsaved_details: RefCell<Option<AppDetails>>,
 
         unsaved_icon: RefCell<Option<Vec<u8>>>,
+        // The image the icon was edited from and how, edits start over from it
+        icon_source: RefCell<Option<Vec<u8>>>,
+        icon_style: RefCell<Option<IconStyle>>,
+
+        profile_rows: RefCell<Vec<adw::EntryRow>>,
+        injection_rows: RefCell<Vec<adw::ActionRow>>,
+        filter_rows: RefCell<Vec<adw::ActionRow>>,
 
         #[template_child]
         pub icon_image: TemplateChild<gtk::Image>,
         #[template_child]
+        pub edit_icon_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub url_entry: TemplateChild<adw::EntryRow>,
         #[template_child]
         pub title_entry: TemplateChild<adw::EntryRow>,
@@ -61,6 +95,44 @@ mod imp {
         pub user_agent_expander: TemplateChild<adw::ExpanderRow>,
         #[template_child]
         pub user_agent_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub scope_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub download_dir_expander: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        pub download_dir_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub proxy_expander: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        pub proxy_url_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub proxy_ignore_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub camera_permission: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub microphone_permission: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub geolocation_permission: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub notifications_permission: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub mute_notifications: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub run_in_background: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub autostart: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub profiles_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub injections_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub filters_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub content_blocking: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub data_size_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub cache_size_row: TemplateChild<adw::ActionRow>,
     }
 
     #[glib::object_subclass]
@@ -109,15 +181,97 @@ mod imp {
             }
         }
         #[template_callback]
+        fn on_add_profile_clicked(&self, _: gtk::Button) {
+            let id = self.details.borrow().id.clone();
+            self.update_profiles(profiles::add_profile(&id));
+        }
+        #[template_callback]
+        fn on_add_injection_clicked(&self, _: gtk::Button) {
+            self.edit_injection(None);
+        }
+        #[template_callback]
+        async fn on_add_filter_list_clicked(&self, _: gtk::Button) {
+            let filter = gtk::FileFilter::new();
+            filter.set_name(Some("Content Blocker Lists"));
+            filter.add_mime_type("application/json");
+
+            let filters = gio::ListStore::new::<gtk::FileFilter>();
+            filters.append(&filter);
+
+            let Some(path) = gtk::FileDialog::builder()
+                .accept_label("Add")
+                .modal(true)
+                .title("Filter List")
+                .filters(&filters)
+                .build()
+                .open_future(self.obj().root().and_downcast_ref::<gtk::Window>())
+                .await
+                .ok()
+                .and_then(|x| x.path())
+            else {
+                return;
+            };
+            let id = self.details.borrow().id.clone();
+            match content_filter::add_filter_list(&id, &path) {
+                Ok(_) => self.setup_filter_lists(),
+                Err(err) => self.toast(err.to_string()),
+            }
+        }
+        #[template_callback]
+        fn on_clear_data_activated(&self, _: adw::ButtonRow) {
+            let dialog = ClearDataDialog::new(&self.details.borrow());
+            dialog.connect_closed(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_| _self.update_storage()
+            ));
+            dialog.present(Some(&*self.obj()));
+        }
+        #[template_callback]
+        fn on_trim_cache_clicked(&self, _: gtk::Button) {
+            let id = self.details.borrow().id.clone();
+            self.obj()
+                .activate_action("win.trim-cache", Some(&id.to_variant()))
+                .unwrap();
+        }
+        #[template_callback]
+        async fn on_download_dir_clicked(&self, _: gtk::Button) {
+            let dialog = gtk::FileDialog::builder()
+                .accept_label("Select")
+                .modal(true)
+                .title("Download Folder")
+                .build();
+            if let Some(path) = dialog
+                .select_folder_future(self.obj().root().and_downcast_ref::<gtk::Window>())
+                .await
+                .ok()
+                .and_then(|x| x.path())
+            {
+                self.download_dir_row
+                    .set_subtitle(path.to_string_lossy().as_ref());
+                self.update_unsaved_details();
+            }
+        }
+        #[template_callback]
         async fn on_icon_clicked(&self, _: gtk::Button) {
             if let Ok(file) =
                 util::icon_from_dialog(self.obj().root().and_downcast_ref::<gtk::Window>()).await
             {
-                if let Err(err) = self.set_unsaved_icon(&file).await {
+                if let Err(err) = self.edit_icon_file(&file).await {
                     self.toast(err.to_string())
                 }
             }
         }
+        #[template_callback]
+        fn on_edit_icon_clicked(&self, _: gtk::Button) {
+            let Some(source) = self.icon_source.borrow().clone().or(self.current_icon()) else {
+                return;
+            };
+            let style = self.icon_style.borrow().clone();
+            if let Err(err) = self.edit_icon(source, style.as_ref()) {
+                self.toast(err.to_string())
+            }
+        }
     }
 
     impl AppPage {
@@ -135,6 +289,11 @@ mod imp {
                 "app.open-app",
                 &details.id,
             ));
+            self.page_menu.append_item(&menu_item_and_target(
+                "Duplicate",
+                "win.duplicate",
+                &details.id,
+            ));
             self.page_menu
                 .append_item(&menu_item_and_target("Delete", "win.delete", &details.id));
             self.page_menu.append_item(&menu_item_and_target(
@@ -142,6 +301,289 @@ mod imp {
                 "win.reinstall",
                 &details.id,
             ));
+            self.page_menu
+                .append_item(&menu_item_and_target("Export", "win.export", &details.id));
+        }
+        fn permission_row(&self, permission: Permission) -> adw::ComboRow {
+            match permission {
+                Permission::Camera => self.camera_permission.get(),
+                Permission::Microphone => self.microphone_permission.get(),
+                Permission::Geolocation => self.geolocation_permission.get(),
+                Permission::Notifications => self.notifications_permission.get(),
+            }
+        }
+        /// Profile changes are saved right away since they also move data on disk
+        /// Only the profiles are taken from the result, so unsaved edits are kept
+        fn update_profiles(&self, result: anyhow::Result<AppDetails>) {
+            match result {
+                Ok(saved) => {
+                    {
+                        let mut details = self.details.borrow_mut();
+                        details.profiles = saved.profiles;
+                        details.profile = saved.profile;
+                    }
+                    self.setup_profiles();
+                    self.update_unsaved_details();
+                }
+                Err(err) => self.toast(err.to_string()),
+            }
+        }
+        fn setup_profiles(&self) {
+            for row in self.profile_rows.take() {
+                self.profiles_group.remove(&row);
+            }
+
+            let details = self.details.borrow();
+            let rows = details
+                .all_profiles()
+                .into_iter()
+                .map(|profile| self.profile_row(&details.id, profile))
+                .collect::<Vec<adw::EntryRow>>();
+            for row in &rows {
+                self.profiles_group.add(row);
+            }
+            self.profile_rows.replace(rows);
+        }
+        fn profile_row(&self, id: &str, profile: Profile) -> adw::EntryRow {
+            let id = id.to_string();
+            let profile_id = profile.id.clone();
+            let is_default = profile_id == DEFAULT_PROFILE;
+            let row = adw::EntryRow::builder()
+                .title("Name")
+                .text(profile.name.as_str())
+                .show_apply_button(true)
+                .editable(!is_default)
+                .build();
+            row.connect_apply(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[strong]
+                id,
+                #[strong]
+                profile_id,
+                move |row| {
+                    _self.update_profiles(profiles::rename_profile(
+                        &id,
+                        &profile_id,
+                        row.text().as_str(),
+                    ));
+                }
+            ));
+
+            let copy_button = gtk::Button::builder()
+                .icon_name("edit-copy-symbolic")
+                .tooltip_text("Copy Profile")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            copy_button.connect_clicked(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[strong]
+                id,
+                #[strong]
+                profile_id,
+                move |_| {
+                    _self.update_profiles(profiles::copy_profile(&id, &profile_id));
+                }
+            ));
+            row.add_suffix(&copy_button);
+
+            // The default profile lives in the app's own directories and can't be removed
+            if !is_default {
+                let delete_button = gtk::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .tooltip_text("Delete Profile")
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat"])
+                    .build();
+                delete_button.connect_clicked(clone!(
+                    #[weak(rename_to=_self)]
+                    self,
+                    #[strong]
+                    id,
+                    move |_| {
+                        _self.confirm_delete_profile(&id, &profile);
+                    }
+                ));
+                row.add_suffix(&delete_button);
+            }
+
+            row
+        }
+        fn confirm_delete_profile(&self, id: &str, profile: &Profile) {
+            let id = id.to_string();
+            let profile_id = profile.id.clone();
+            let confirm_dialog = adw::AlertDialog::new(
+                Some(format!("Delete {}?", profile.name).as_str()),
+                Some("Logins and website data of this profile will be lost."),
+            );
+            confirm_dialog.add_responses(&[("delete", "Delete"), ("cancel", "Cancel")]);
+            confirm_dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
+            confirm_dialog.connect_response(
+                Some("delete"),
+                clone!(
+                    #[weak(rename_to=_self)]
+                    self,
+                    #[strong]
+                    id,
+                    #[strong]
+                    profile_id,
+                    move |_, _| {
+                        _self.update_profiles(profiles::delete_profile(&id, &profile_id));
+                    }
+                ),
+            );
+            confirm_dialog.present(Some(&*self.obj()));
+        }
+        /// Like profiles, injections are saved right away since their sources are files
+        fn update_injections(&self, result: anyhow::Result<AppDetails>) {
+            match result {
+                Ok(saved) => {
+                    self.details.borrow_mut().injections = saved.injections;
+                    self.setup_injections();
+                    self.update_unsaved_details();
+                }
+                Err(err) => self.toast(err.to_string()),
+            }
+        }
+        fn setup_injections(&self) {
+            for row in self.injection_rows.take() {
+                self.injections_group.remove(&row);
+            }
+
+            let details = self.details.borrow();
+            let rows = details
+                .injections
+                .iter()
+                .map(|injection| self.injection_row(&details.id, injection))
+                .collect::<Vec<adw::ActionRow>>();
+            for row in &rows {
+                self.injections_group.add(row);
+            }
+            self.injection_rows.replace(rows);
+        }
+        fn injection_row(&self, id: &str, injection: &Injection) -> adw::ActionRow {
+            let id = id.to_string();
+            let injection_id = injection.id.clone();
+            let row = adw::ActionRow::builder()
+                .title(injection.name.as_str())
+                .subtitle(injection.describe().as_str())
+                .activatable(true)
+                .build();
+            let injection = injection.clone();
+            row.connect_activated(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[strong]
+                injection,
+                move |_| _self.edit_injection(Some(&injection))
+            ));
+
+            let delete_button = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Delete")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            delete_button.connect_clicked(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[strong]
+                id,
+                #[strong]
+                injection_id,
+                move |_| {
+                    _self.update_injections(injections::delete_injection(&id, &injection_id));
+                }
+            ));
+            row.add_suffix(&delete_button);
+            row.add_suffix(&gtk::Image::from_icon_name("go-next-symbolic"));
+
+            row
+        }
+        fn edit_injection(&self, injection: Option<&Injection>) {
+            let dialog = InjectionDialog::new(&self.details.borrow().id, injection);
+            dialog.connect_saved(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_| {
+                    let id = _self.details.borrow().id.clone();
+                    _self.update_injections(
+                        apps::get_app_details(&id).ok_or(anyhow::anyhow!("No app with id {id}")),
+                    );
+                }
+            ));
+            dialog.present(Some(&*self.obj()));
+        }
+        fn setup_filter_lists(&self) {
+            for row in self.filter_rows.take() {
+                self.filters_group.remove(&row);
+            }
+
+            let id = self.details.borrow().id.clone();
+            let rows = content_filter::user_filter_lists(&id)
+                .into_iter()
+                .map(|path| {
+                    let row = adw::ActionRow::builder()
+                        .title(
+                            path.file_stem()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .as_ref(),
+                        )
+                        .build();
+                    let delete_button = gtk::Button::builder()
+                        .icon_name("user-trash-symbolic")
+                        .tooltip_text("Remove")
+                        .valign(gtk::Align::Center)
+                        .css_classes(["flat"])
+                        .build();
+                    delete_button.connect_clicked(clone!(
+                        #[weak(rename_to=_self)]
+                        self,
+                        move |_| match content_filter::remove_filter_list(&path) {
+                            Ok(_) => _self.setup_filter_lists(),
+                            Err(err) => _self.toast(err.to_string()),
+                        }
+                    ));
+                    row.add_suffix(&delete_button);
+                    row
+                })
+                .collect::<Vec<adw::ActionRow>>();
+            for row in &rows {
+                self.filters_group.add(row);
+            }
+            self.filter_rows.replace(rows);
+        }
+        pub fn update_storage(&self) {
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                async move {
+                    let id = _self.details.borrow().id.clone();
+                    let size = apps::get_app_size(&id).await;
+                    _self
+                        .data_size_row
+                        .set_subtitle(&glib::format_size(size.data));
+                    _self
+                        .cache_size_row
+                        .set_subtitle(&glib::format_size(size.cache));
+                }
+            ));
+        }
+        fn unsaved_proxy(&self) -> ProxyMode {
+            let url = self.proxy_url_entry.text().trim().to_string();
+            if !self.proxy_expander.enables_expansion() {
+                ProxyMode::System
+            } else if url.is_empty() {
+                ProxyMode::None
+            } else {
+                ProxyMode::Custom {
+                    url,
+                    ignore_hosts: scope::parse_scope(self.proxy_ignore_entry.text().as_str()),
+                }
+            }
         }
         fn diff_significance(&self) -> DiffSignificance {
             let unsaved = self.unsaved_details.borrow().clone().unwrap();
@@ -171,6 +613,26 @@ mod imp {
                     .user_agent_expander
                     .enables_expansion()
                     .then(|| self.user_agent_entry.text().to_string()),
+                scope: scope::parse_scope(self.scope_entry.text().as_str()),
+                proxy: self.unsaved_proxy(),
+                download_dir: self
+                    .download_dir_expander
+                    .enables_expansion()
+                    .then(|| self.download_dir_row.subtitle())
+                    .flatten()
+                    .map(|x| x.to_string())
+                    .filter(|x| !x.is_empty()),
+                permissions: Permission::ALL
+                    .into_iter()
+                    .filter_map(|permission| {
+                        index_to_permission(self.permission_row(permission).selected())
+                            .map(|allowed| (permission, allowed))
+                    })
+                    .collect(),
+                mute_notifications: self.mute_notifications.is_active(),
+                run_in_background: self.run_in_background.is_active(),
+                content_blocking: self.content_blocking.is_active(),
+                autostart: self.autostart.is_active(),
                 icon,
                 ..details
             };
@@ -189,6 +651,9 @@ mod imp {
             let wid = WindowIdentifier::from_native(&self.obj().root().unwrap()).await;
             let unsaved_details = self.unsaved_details.borrow().clone();
             if let Some(unsaved_details) = unsaved_details {
+                unsaved_details.proxy.validate()?;
+                let autostart_changed =
+                    unsaved_details.autostart != self.details.borrow().autostart;
                 match self.diff_significance() {
                     DiffSignificance::Settings => {
                         unsaved_details.save()?;
@@ -203,6 +668,9 @@ mod imp {
                     }
                     _ => (),
                 }
+                if autostart_changed {
+                    apps::update_autostart(Some(wid)).await?;
+                }
                 self.set_details(&unsaved_details);
             }
             self.update_unsaved_details();
@@ -213,12 +681,18 @@ mod imp {
             let details = self.details.borrow().clone();
             self.set_details(&details);
             self.unsaved_icon.replace(None);
+            self.icon_source.replace(None);
+            self.icon_style.replace(None);
+            self.edit_icon_button
+                .set_sensitive(self.current_icon().is_some());
             self.update_unsaved_details();
         }
         pub fn set_details(&self, details: &AppDetails) {
             self.details.replace(details.clone());
             self.icon_image
                 .set_paintable(Some(&details.to_gdk_texture(256)));
+            self.edit_icon_button
+                .set_sensitive(self.current_icon().is_some());
             self.title_entry.set_text(details.title.as_str());
             self.url_entry.set_text(details.url.as_str());
             self.titlebar_color.set_active(details.has_titlebar_color);
@@ -227,24 +701,70 @@ mod imp {
             if let Some(user_agent) = &details.user_agent {
                 self.user_agent_entry.set_text(user_agent.as_str());
             }
+            self.scope_entry.set_text(details.scope.join(", ").as_str());
+            self.download_dir_expander
+                .set_enable_expansion(details.download_dir.is_some());
+            self.download_dir_row
+                .set_subtitle(details.download_dir.as_deref().unwrap_or(""));
+            self.proxy_expander
+                .set_enable_expansion(details.proxy != ProxyMode::System);
+            if let ProxyMode::Custom { url, ignore_hosts } = &details.proxy {
+                self.proxy_url_entry.set_text(url.as_str());
+                self.proxy_ignore_entry
+                    .set_text(ignore_hosts.join(", ").as_str());
+            } else {
+                self.proxy_url_entry.set_text("");
+                self.proxy_ignore_entry.set_text("");
+            }
+            self.mute_notifications
+                .set_active(details.mute_notifications);
+            self.run_in_background.set_active(details.run_in_background);
+            self.autostart.set_active(details.autostart);
+            self.content_blocking.set_active(details.content_blocking);
+            for permission in Permission::ALL {
+                self.permission_row(permission)
+                    .set_selected(permission_to_index(
+                        details.permissions.get(&permission).copied(),
+                    ));
+            }
 
             self.setup_menu();
+            self.setup_profiles();
+            self.setup_injections();
+            self.setup_filter_lists();
+            self.update_storage();
         }
-        async fn set_unsaved_icon(&self, file: &gio::File) -> anyhow::Result<()> {
+        async fn edit_icon_file(&self, file: &gio::File) -> anyhow::Result<()> {
             let (buffer, _etag) = file.load_contents_future().await?;
-            let extension = file.basename();
-            let extension = extension
-                .as_ref()
-                .and_then(|x| x.extension())
-                .and_then(|x| x.to_str());
-            let image =
-                util::Image::from_buffer(buffer.to_vec(), extension.is_some_and(|x| x == "svg"))?;
-            self.unsaved_icon.replace(Some(image.buffer.to_vec()));
-            self.icon_image
-                .set_paintable(Some(&image.to_gdk_texture(32)));
-            self.update_unsaved_details();
+            self.edit_icon(buffer.to_vec(), None)
+        }
+
+        /// Opens the icon editor, the edited icon becomes an unsaved change
+        fn edit_icon(&self, source: Vec<u8>, style: Option<&IconStyle>) -> anyhow::Result<()> {
+            let theme_color = self.details.borrow().theme_color.clone();
+            let dialog = IconEditorDialog::new(&source, theme_color.as_deref(), style)?;
+            dialog.connect_saved(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |dialog, icon| {
+                    _self
+                        .icon_image
+                        .set_paintable(Some(&util::to_gdk_texture(&icon, 32)));
+                    _self.unsaved_icon.replace(Some(icon));
+                    _self.icon_source.replace(Some(source.clone()));
+                    _self.icon_style.replace(Some(dialog.style()));
+                    _self.update_unsaved_details();
+                }
+            ));
+            dialog.present(Some(&*self.obj()));
             Ok(())
         }
+
+        fn current_icon(&self) -> Option<Vec<u8>> {
+            let icon = self.unsaved_icon.borrow().clone();
+            icon.or(self.details.borrow().icon.clone())
+        }
+
         fn setup_signals(&self) {
             self.titlebar_color.connect_active_notify(clone!(
                 #[weak(rename_to=_self)]
@@ -270,4 +790,8 @@ impl AppPage {
         imp.set_details(&details);
         obj
     }
+
+    pub fn update_storage(&self) {
+        self.imp().update_storage();
+    }
 }
@@ -5,7 +5,7 @@ use glib::Object;
 use gtk::glib;
 use std::cell::{OnceCell, RefCell};
 
-use crate::apps::{get_app_details, get_app_icon, AppDetails};
+use crate::apps::{get_app_details, get_app_icon, get_app_size, AppDetails};
 
 mod imp {
 
@@ -21,6 +21,8 @@ mod imp {
         pub title: TemplateChild<gtk::Label>,
         #[template_child]
         pub subtitle: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub size_label: TemplateChild<gtk::Label>,
 
         #[property(get, set = Self::on_id_set)]
         pub id: RefCell<String>,
@@ -68,6 +70,24 @@ mod imp {
                     _self.icon.set_paintable(Some(&details.to_gdk_texture(64)));
                 }
             ));
+            self.update_size();
+        }
+
+        pub fn update_size(&self) {
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to = _self)]
+                self,
+                async move {
+                    let id = _self.id.borrow().clone();
+                    let size = get_app_size(id.as_str()).await;
+                    _self.size_label.set_label(&glib::format_size(size.total()));
+                    _self.size_label.set_tooltip_text(Some(&format!(
+                        "Data: {}, Cache: {}",
+                        glib::format_size(size.data),
+                        glib::format_size(size.cache)
+                    )));
+                }
+            ));
         }
     }
 }
@@ -82,4 +102,9 @@ impl AppRow {
     pub fn new(id: String) -> Self {
         Object::builder().property("id", id).build()
     }
+
+    /// Measures the app's disk usage again, after its cache was trimmed
+    pub fn update_size(&self) {
+        self.imp().update_size();
+    }
 }
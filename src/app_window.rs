@@ -3,11 +3,19 @@ use adw::subclass::prelude::*;
 use glib::clone;
 use gtk::{gdk, gio, glib};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use webkit::prelude::*;
 use webkit::soup;
 use webkit::{HardwareAccelerationPolicy, PolicyDecisionType, WebContext, WebView};
 
 use crate::apps::{get_app_details, AppDetails};
+use crate::content_filter;
+use crate::download_row::{DownloadRow, DownloadState};
+use crate::injections;
+use crate::permissions::{self, Permission};
+use crate::profiles::{self, DEFAULT_PROFILE};
+use crate::{popup_window, scope};
 
 fn format_css(id: &str, bg: &str, fg: &str) -> String {
     format!(
@@ -18,6 +26,35 @@ fn format_css(id: &str, bg: &str, fg: &str) -> String {
     )
 }
 
+// Same steps as most browsers
+const ZOOM_LEVELS: [f64; 15] = [
+    0.3, 0.5, 0.67, 0.8, 0.9, 1.0, 1.1, 1.2, 1.33, 1.5, 1.7, 2.0, 2.4, 3.0, 4.0,
+];
+
+/// Matches past this are only counted as "more"
+const MAX_MATCH_COUNT: u32 = 1000;
+
+/// Picks a path in the directory that doesn't exist yet by numbering the file name
+fn unique_destination(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let name = Path::new(name);
+    let stem = name
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{stem} ({n}){extension}")))
+        .find(|x| !x.exists())
+        .unwrap()
+}
+
 // Source: https://www.w3.org/WAI/GL/wiki/Relative_luminance
 fn luminence(rgba: gdk::RGBA) -> f32 {
     0.2126 * rgba.red() + 0.7152 * rgba.green() + 0.0722 * rgba.blue()
@@ -40,10 +77,30 @@ mod imp {
         pub back_button: TemplateChild<gtk::Button>,
         #[template_child]
         pub forward_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub profile_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub zoom_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub downloads_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub downloads_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub injections_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub search_bar: TemplateChild<gtk::SearchBar>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub match_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub case_button: TemplateChild<gtk::ToggleButton>,
 
         pub details: RefCell<AppDetails>,
         pub webview: RefCell<webkit::WebView>,
         pub provider: RefCell<Option<gtk::CssProvider>>,
+        pub notifications: RefCell<HashMap<u64, webkit::Notification>>,
+        pub injections_monitor: RefCell<Option<gio::FileMonitor>>,
     }
 
     #[glib::object_subclass]
@@ -64,19 +121,39 @@ mod imp {
     impl ObjectImpl for AppWindow {
         fn constructed(&self) {
             self.parent_constructed();
+            self.search_bar.connect_entry(&*self.search_entry);
             self.obj().setup_gestures();
             self.obj().setup_gactions();
+
+            // Page controls are in the way when watching something fullscreen
+            self.obj().connect_fullscreened_notify(|win| {
+                win.imp().toolbar.set_reveal_top_bars(!win.is_fullscreen());
+            });
+            self.obj().connect_focus_widget_notify(|win| {
+                let webview = win.imp().webview.borrow().clone();
+                win.imp().update_stop_action(&webview);
+            });
         }
     }
     impl WidgetImpl for AppWindow {}
     impl WindowImpl for AppWindow {
         fn close_request(&self) -> glib::Propagation {
             let size = self.obj().default_size();
+            let mut run_in_background = false;
             if let Some(mut details) = get_app_details(&self.details.borrow().id) {
                 details.window_width = size.0;
                 details.window_height = size.1;
                 details.window_maximize = self.obj().is_maximized();
+                details.zoom_level = self.webview.borrow().zoom_level();
                 details.save().unwrap(); // App is closing, shouldn't fail really ever
+                run_in_background = details.run_in_background;
+            }
+
+            // Keep the window (and with it the WebView) alive so the page can
+            // continue running and sending notifications
+            if run_in_background {
+                self.obj().set_visible(false);
+                return glib::Propagation::Stop;
             }
             glib::Propagation::Proceed
         }
@@ -94,16 +171,102 @@ mod imp {
             self.obj().set_title(Some(details.title.as_str()));
             self.obj().load_window_size();
 
-            // Set up the WebView
+            self.load_webview();
+            self.load_profiles();
+            self.load_colors(None);
+            self.watch_injections();
+        }
+
+        fn load_webview(&self) {
+            // The search belongs to the old WebView's find controller
+            self.search_bar.set_search_mode(false);
             let webview = self.create_webview();
-            webview.load_uri(&details.url);
             self.webview_container.set_child(Some(&webview));
-            self.webview.replace(webview);
+            self.webview.replace(webview.clone());
 
-            self.load_colors(None);
+            // Filters have to be in place before the first request goes out
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[weak]
+                webview,
+                async move {
+                    _self.load_content_filters(&webview).await;
+                    webview.load_uri(&_self.details.borrow().url);
+                }
+            ));
+        }
+
+        async fn load_content_filters(&self, webview: &WebView) {
+            let (id, content_blocking) = {
+                let details = self.details.borrow();
+                (details.id.clone(), details.content_blocking)
+            };
+            if !content_blocking {
+                return;
+            }
+            let Some(content_manager) = webview.user_content_manager() else {
+                return;
+            };
+            for filter in content_filter::load_filters(&id).await {
+                content_manager.add_filter(&filter);
+            }
+        }
+
+        fn load_profiles(&self) {
+            let details = self.details.borrow();
+            let menu = gio::Menu::new();
+            for profile in details.all_profiles() {
+                let item = gio::MenuItem::new(Some(profile.name.as_str()), None);
+                item.set_action_and_target_value(
+                    Some("win.switch-profile"),
+                    Some(&profile.id.to_variant()),
+                );
+                menu.append_item(&item);
+            }
+            self.profile_button.set_menu_model(Some(&menu));
+            // There is nothing to switch between with only the default profile
+            self.profile_button
+                .set_visible(!details.profiles.is_empty());
+            if let Some(action) = self
+                .obj()
+                .lookup_action("switch-profile")
+                .and_downcast::<gio::SimpleAction>()
+            {
+                action.set_state(&details.profile.to_variant());
+            }
+        }
+
+        pub fn switch_profile(&self, profile: &str) {
+            {
+                let mut details = self.details.borrow_mut();
+                if details.profile == profile
+                    || !details.all_profiles().iter().any(|x| x.id == profile)
+                {
+                    return;
+                }
+                details.profile = profile.to_string();
+                // Keep the zoom the user picked in the new WebView
+                details.zoom_level = self.webview.borrow().zoom_level();
+                // Reopen with the last used profile next time
+                if let Some(mut saved) = get_app_details(&details.id) {
+                    saved.profile = details.profile.clone();
+                    if let Err(err) = saved.save() {
+                        eprintln!("Failed to save profile: {err}");
+                    }
+                }
+            }
+            self.load_webview();
+            self.load_profiles();
         }
 
         fn load_colors(&self, bg: Option<&str>) {
+            // Fall back to the theme color from the app's manifest when the page has none
+            let details = self.details.borrow();
+            let bg = bg.or(details
+                .theme_color
+                .as_deref()
+                .filter(|_| details.has_titlebar_color));
             if self.provider.borrow().is_none() {
                 let display = gdk::Display::default().unwrap();
                 let provider = gtk::CssProvider::new();
@@ -140,17 +303,6 @@ mod imp {
         }
         fn create_webview(&self) -> webkit::WebView {
             let details = self.details.borrow();
-            let id = details.id.as_str();
-
-            // Define and create base app directory where webkit cache, data, and cookies are stored
-            let app_data_dir = glib::user_data_dir()
-                .join(glib::application_name().unwrap())
-                .join(id);
-            let app_cache_dir = glib::user_cache_dir()
-                .join(glib::application_name().unwrap())
-                .join(id);
-            std::fs::create_dir_all(app_data_dir.clone()).unwrap();
-            std::fs::create_dir_all(app_cache_dir.clone()).unwrap();
 
             // Build settings
             let mut settings = webkit::Settings::builder()
@@ -173,58 +325,18 @@ mod imp {
             }
             let settings = settings.build();
 
-            // Build network session
-            let network_session = webkit::NetworkSession::builder()
-                .cache_directory(app_cache_dir.to_str().unwrap())
-                .data_directory(app_data_dir.join("data").to_str().unwrap())
-                .build();
+            // Build network session, this is where the profile's cache, data, and cookies are stored
+            let network_session = profiles::build_network_session(&details);
 
-            network_session.connect_download_started(|_, dl| {
-                dl.connect_decide_destination(move |dl, dest| {
-                    let dest = dest.to_string();
-                    glib::spawn_future_local(clone!(
-                        #[weak]
-                        dl,
-                        async move {
-                            let dialog = gtk::FileDialog::builder()
-                                .accept_label("Save")
-                                .title("Download file")
-                                .modal(false)
-                                .initial_name(dest.as_str())
-                                .build();
-                            if let Some(path) = dialog
-                                .save_future(None::<&gtk::Window>)
-                                .await
-                                .ok()
-                                .and_then(|f| f.path())
-                            {
-                                let path = path.as_os_str().to_str().unwrap();
-                                dl.set_destination(path);
-                            }
-                        }
-                    ));
-                    true
-                });
-            });
-
-            // Build cookie manager
-            let cookie_manager = network_session.cookie_manager().unwrap();
-
-            cookie_manager.set_persistent_storage(
-                app_data_dir.join("cookie").to_str().unwrap(),
-                webkit::CookiePersistentStorage::Sqlite,
-            );
+            network_session.connect_download_started(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, download| _self.handle_download(download)
+            ));
 
             // Build content manager
             let content_manager = webkit::UserContentManager::new();
             if details.has_titlebar_color {
-                let script = webkit::UserScript::new(
-                    include_str!("./inject.js"),
-                    webkit::UserContentInjectedFrames::TopFrame,
-                    webkit::UserScriptInjectionTime::End,
-                    &[],
-                    &[],
-                );
                 content_manager.register_script_message_handler("themeColor", None);
                 content_manager.connect_script_message_received(
                     Some("themeColor"),
@@ -241,8 +353,8 @@ mod imp {
                         }
                     ),
                 );
-                content_manager.add_script(&script);
             }
+            self.load_user_content(&content_manager);
 
             // Build WebContext
             let web_context = WebContext::new();
@@ -257,49 +369,75 @@ mod imp {
                 .web_context(&web_context)
                 .build();
 
-            webview.connect_decide_policy(|webview, decision, decision_type| {
-                if decision_type == PolicyDecisionType::NewWindowAction {
+            // Connected first so that they decide on popups and downloads before the handler below
+            let allowed_hosts = details.allowed_hosts();
+            popup_window::handle_popups(&webview, allowed_hosts.clone());
+            popup_window::handle_downloads(&webview);
+            webview.connect_decide_policy(move |webview, decision, decision_type| {
+                if decision_type == PolicyDecisionType::NavigationAction {
                     if let Some(mut action) = decision
                         .clone()
                         .downcast::<webkit::NavigationPolicyDecision>()
                         .ok()
                         .and_then(|x| x.navigation_action())
                     {
+                        // Only user clicked links are sent out of the app, redirects and
+                        // scripted navigations are left alone so OAuth flows can return
                         if let Some(uri) = action.request().and_then(|a| a.uri()) {
-                            open::that_detached(uri).unwrap();
+                            if action.navigation_type() == webkit::NavigationType::LinkClicked
+                                && !action.is_redirect()
+                                && !scope::is_in_scope(&uri, &allowed_hosts)
+                                && !scope::is_auth_provider(&uri)
+                                && !webview
+                                    .uri()
+                                    .is_some_and(|current| scope::is_same_host(&current, &uri))
+                            {
+                                open::that_detached(uri).unwrap();
 
-                            decision.ignore();
-                            return false;
-                        }
-                    }
-                }
-                if decision_type == PolicyDecisionType::Response {
-                    if let Some(headers) = decision
-                        .clone()
-                        .downcast::<webkit::ResponsePolicyDecision>()
-                        .ok()
-                        .and_then(|x| x.response())
-                        .and_then(|x| x.http_headers())
-                    {
-                        if headers
-                            .one("Content-Type")
-                            .and_then(|x| {
-                                if webview.can_show_mime_type(x.as_str()) {
-                                    Some(())
-                                } else {
-                                    None
-                                }
-                            })
-                            .is_none()
-                        {
-                            decision.download();
-                            return true;
+                                decision.ignore();
+                                return true;
+                            }
                         }
                     }
                 }
                 true
             });
 
+            webview.connect_permission_request(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[upgrade_or]
+                false,
+                move |webview, request| {
+                    _self.handle_permission_request(webview, request);
+                    true
+                }
+            ));
+
+            webview.connect_show_notification(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[upgrade_or]
+                false,
+                move |_, notification| _self.show_notification(notification)
+            ));
+
+            self.setup_find_controller(&webview);
+
+            webview.set_zoom_level(details.zoom_level);
+            self.update_zoom_button(&webview);
+            webview.connect_zoom_level_notify(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |webview: &WebView| _self.update_zoom_button(webview)
+            ));
+
+            webview.connect_is_loading_notify(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |webview: &WebView| _self.update_stop_action(webview)
+            ));
+
             webview.connect_estimated_load_progress_notify(clone!(
                 #[weak(rename_to=_self)]
                 self,
@@ -322,6 +460,295 @@ mod imp {
             webview
         }
 
+        /// Adds the scripts and style sheets, replacing any that were added before
+        fn load_user_content(&self, content_manager: &webkit::UserContentManager) {
+            let details = self.details.borrow();
+            content_manager.remove_all_scripts();
+            content_manager.remove_all_style_sheets();
+            if details.has_titlebar_color {
+                content_manager.add_script(&webkit::UserScript::new(
+                    include_str!("./inject.js"),
+                    webkit::UserContentInjectedFrames::TopFrame,
+                    webkit::UserScriptInjectionTime::End,
+                    &[],
+                    &[],
+                ));
+            }
+            injections::add_to_content_manager(&details.id, &details.injections, content_manager);
+        }
+
+        // Every change to the injections rewrites or removes their files, so
+        // watching the directory is enough to know when to offer a reload
+        fn watch_injections(&self) {
+            let dir = gio::File::for_path(injections::injections_dir(&self.details.borrow().id));
+            match dir.monitor_directory(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+                Ok(monitor) => {
+                    monitor.connect_changed(clone!(
+                        #[weak(rename_to=_self)]
+                        self,
+                        move |_, _, _, _| _self.injections_banner.set_revealed(true)
+                    ));
+                    self.injections_monitor.replace(Some(monitor));
+                }
+                Err(err) => eprintln!("Failed to watch custom styles and scripts: {err}"),
+            }
+        }
+
+        fn reload_injections(&self) {
+            let id = self.details.borrow().id.clone();
+            if let Some(saved) = get_app_details(&id) {
+                self.details.borrow_mut().injections = saved.injections;
+            }
+            let webview = self.webview.borrow();
+            if let Some(content_manager) = webview.user_content_manager() {
+                self.load_user_content(&content_manager);
+            }
+            webview.reload();
+            self.injections_banner.set_revealed(false);
+        }
+
+        fn handle_download(&self, download: &webkit::Download) {
+            let row = DownloadRow::new(download);
+            self.downloads_list.prepend(&row);
+            self.downloads_button.set_visible(true);
+
+            let download_dir = self.details.borrow().download_dir.clone();
+            download.connect_decide_destination(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[upgrade_or]
+                false,
+                move |download, suggested| {
+                    if let Some(download_dir) = &download_dir {
+                        let path = unique_destination(Path::new(download_dir), suggested);
+                        download.set_destination(path.to_str().unwrap());
+                        return true;
+                    }
+                    let suggested = suggested.to_string();
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        _self,
+                        #[weak]
+                        download,
+                        async move {
+                            let dialog = gtk::FileDialog::builder()
+                                .accept_label("Save")
+                                .title("Download file")
+                                .modal(false)
+                                .initial_name(suggested.as_str())
+                                .build();
+                            match dialog
+                                .save_future(Some(&*_self.obj()))
+                                .await
+                                .ok()
+                                .and_then(|f| f.path())
+                            {
+                                Some(path) => {
+                                    let path = path.as_os_str().to_str().unwrap();
+                                    download.set_destination(path);
+                                }
+                                // Otherwise the download would wait for a destination forever
+                                None => download.cancel(),
+                            }
+                        }
+                    ));
+                    true
+                }
+            ));
+            download.connect_finished(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[weak]
+                row,
+                move |download| {
+                    if row.state() == DownloadState::Finished {
+                        _self.notify_download_finished(download);
+                    }
+                }
+            ));
+        }
+
+        fn notify_download_finished(&self, download: &webkit::Download) {
+            let (Some(app), Some(destination)) = (self.obj().application(), download.destination())
+            else {
+                return;
+            };
+            let name = Path::new(destination.as_str())
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let notification = gio::Notification::new("Download finished");
+            notification.set_body(Some(name.as_str()));
+            notification.set_default_action_and_target_value(
+                "app.open-file",
+                Some(&destination.as_str().to_variant()),
+            );
+            app.send_notification(None, &notification);
+        }
+
+        pub fn handle_permission_request(
+            &self,
+            webview: &WebView,
+            request: &webkit::PermissionRequest,
+        ) {
+            let requested = Permission::from_request(request);
+            if requested.is_empty() {
+                request.deny();
+                return;
+            }
+
+            let decisions: Vec<Option<bool>> = {
+                let details = self.details.borrow();
+                requested
+                    .iter()
+                    .map(|x| details.permissions.get(x).copied())
+                    .collect()
+            };
+            if let Some(allowed) = permissions::decide(&decisions) {
+                permissions::request_decided(request, allowed);
+                return;
+            }
+
+            let site = webview
+                .uri()
+                .and_then(|x| url::Url::parse(&x).ok())
+                .and_then(|x| x.host_str().map(|x| x.to_string()))
+                .unwrap_or_else(|| self.details.borrow().title.clone());
+            let dialog = adw::AlertDialog::new(
+                Some(format!("Allow {site} to {}?", permissions::describe(&requested)).as_str()),
+                Some("Your choice will be remembered and can be changed from Spider."),
+            );
+            dialog.add_responses(&[("deny", "Deny"), ("allow", "Allow")]);
+            dialog.set_response_appearance("allow", adw::ResponseAppearance::Suggested);
+            dialog.set_close_response("deny");
+            let request = request.clone();
+            dialog.connect_response(
+                None,
+                clone!(
+                    #[weak(rename_to=_self)]
+                    self,
+                    #[strong]
+                    request,
+                    move |_, response| {
+                        let allowed = response == "allow";
+                        _self.remember_permissions(&requested, allowed);
+                        permissions::request_decided(&request, allowed);
+                    }
+                ),
+            );
+            dialog.present(Some(&*self.obj()));
+        }
+
+        fn remember_permissions(&self, requested: &[Permission], allowed: bool) {
+            let mut details = self.details.borrow_mut();
+            for permission in requested {
+                details.permissions.insert(*permission, allowed);
+            }
+            // Reload the stored details so that only the permissions are touched
+            if let Some(mut saved) = get_app_details(&details.id) {
+                saved.permissions = details.permissions.clone();
+                if let Err(err) = saved.save() {
+                    eprintln!("Failed to save permissions: {err}");
+                }
+            }
+        }
+
+        fn show_notification(&self, notification: &webkit::Notification) -> bool {
+            // Muted notifications are still claimed so WebKit doesn't show them itself
+            if self.details.borrow().mute_notifications {
+                return true;
+            }
+            let Some(app) = self.obj().application() else {
+                return false;
+            };
+
+            let id = notification.id();
+            let key = format!("web-notification-{id}");
+            let gnotification = gio::Notification::new(notification.title().as_str());
+            gnotification.set_body(Some(notification.body().as_str()));
+            gnotification.set_default_action_and_target_value(
+                "app.notification-clicked",
+                Some(&id.to_variant()),
+            );
+            app.send_notification(Some(key.as_str()), &gnotification);
+
+            notification.connect_closed(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[weak]
+                app,
+                move |_| {
+                    app.withdraw_notification(key.as_str());
+                    _self.notifications.borrow_mut().remove(&id);
+                }
+            ));
+            self.notifications
+                .borrow_mut()
+                .insert(id, notification.clone());
+            true
+        }
+
+        fn setup_find_controller(&self, webview: &WebView) {
+            let controller = webview.find_controller().unwrap();
+            controller.connect_counted_matches(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, count| _self.set_match_count(count)
+            ));
+            controller.connect_found_text(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, _| _self.search_entry.remove_css_class("error")
+            ));
+            controller.connect_failed_to_find_text(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_| {
+                    _self.search_entry.add_css_class("error");
+                    _self.set_match_count(0);
+                }
+            ));
+        }
+
+        fn find_options(&self) -> u32 {
+            let mut options = webkit::FindOptions::WRAP_AROUND;
+            if !self.case_button.is_active() {
+                options |= webkit::FindOptions::CASE_INSENSITIVE;
+            }
+            options.bits()
+        }
+
+        fn search(&self) {
+            let Some(controller) = self.webview.borrow().find_controller() else {
+                return;
+            };
+            let text = self.search_entry.text();
+            if text.is_empty() {
+                controller.search_finish();
+                self.search_entry.remove_css_class("error");
+                self.match_label.set_label("");
+                return;
+            }
+            controller.count_matches(text.as_str(), self.find_options(), MAX_MATCH_COUNT);
+            controller.search(text.as_str(), self.find_options(), MAX_MATCH_COUNT);
+        }
+
+        fn set_match_count(&self, count: u32) {
+            let label = match count {
+                0 => "No matches".to_string(),
+                1 => "1 match".to_string(),
+                count if count > MAX_MATCH_COUNT => format!("{MAX_MATCH_COUNT}+ matches"),
+                count => format!("{count} matches"),
+            };
+            self.match_label.set_label(label.as_str());
+        }
+
+        pub fn find(&self) {
+            self.search_bar.set_search_mode(true);
+            self.search_entry.grab_focus();
+            self.search_entry.select_region(0, -1);
+        }
+
         pub fn go_back(&self) {
             let webview = self.webview.borrow();
             webview.go_back();
@@ -332,6 +759,76 @@ mod imp {
             webview.go_forward();
             self.update_nav_buttons(&webview);
         }
+        pub fn go_home(&self) {
+            let url = self.details.borrow().url.clone();
+            self.webview.borrow().load_uri(&url);
+        }
+        pub fn reload(&self, bypass_cache: bool) {
+            let webview = self.webview.borrow();
+            if bypass_cache {
+                webview.reload_bypass_cache();
+            } else {
+                webview.reload();
+            }
+        }
+        pub fn stop(&self) {
+            self.webview.borrow().stop_loading();
+        }
+
+        /// Steps to the next zoom level in the given direction, or back to 100% when 0
+        pub fn zoom(&self, direction: i32) {
+            let webview = self.webview.borrow();
+            let current = webview.zoom_level();
+            let level = match direction.signum() {
+                1 => ZOOM_LEVELS.iter().find(|x| **x > current + 0.01),
+                -1 => ZOOM_LEVELS.iter().rev().find(|x| **x < current - 0.01),
+                _ => Some(&1.0),
+            };
+            if let Some(level) = level {
+                webview.set_zoom_level(*level);
+            }
+        }
+
+        fn update_zoom_button(&self, webview: &WebView) {
+            let zoom_level = webview.zoom_level();
+            self.zoom_button
+                .set_label(format!("{:.0}%", zoom_level * 100.0).as_str());
+            self.zoom_button
+                .set_visible((zoom_level - 1.0).abs() > 0.01);
+        }
+
+        pub fn copy_url(&self) {
+            if let Some(uri) = self.webview.borrow().uri() {
+                self.obj().clipboard().set_text(uri.as_str());
+            }
+        }
+        pub fn print(&self) {
+            let operation = webkit::PrintOperation::new(&*self.webview.borrow());
+            operation.run_dialog(Some(&*self.obj()));
+        }
+        pub fn toggle_fullscreen(&self) {
+            let obj = self.obj();
+            obj.set_fullscreened(!obj.is_fullscreen());
+        }
+        pub fn show_inspector(&self) {
+            if let Some(inspector) = self.webview.borrow().inspector() {
+                inspector.show();
+            }
+        }
+
+        // Only let Escape stop the page while loading, so the page still gets it otherwise
+        // The find bar keeps it too, Escape closes the bar while it has focus
+        fn update_stop_action(&self, webview: &WebView) {
+            let finding =
+                GtkWindowExt::focus(&*self.obj()).is_some_and(|x| x.is_ancestor(&*self.search_bar));
+            if let Some(action) = self
+                .obj()
+                .lookup_action("stop")
+                .and_downcast::<gio::SimpleAction>()
+            {
+                action.set_enabled(webview.is_loading() && !finding);
+            }
+        }
 
         fn update_nav_buttons(&self, webview: &WebView) {
             // The back_forward_list hasn't updated by this point
@@ -364,6 +861,44 @@ mod imp {
         fn on_forward_clicked(&self, _: gtk::Button) {
             self.go_forward();
         }
+        #[template_callback]
+        fn on_reload_injections_clicked(&self, _: adw::Banner) {
+            self.reload_injections();
+        }
+        #[template_callback]
+        fn on_search_changed(&self, _: gtk::SearchEntry) {
+            self.search();
+        }
+        #[template_callback]
+        fn on_case_toggled(&self, _: gtk::ToggleButton) {
+            self.search();
+        }
+        #[template_callback]
+        fn on_search_next(&self, _: gtk::Widget) {
+            if let Some(controller) = self.webview.borrow().find_controller() {
+                controller.search_next();
+            }
+        }
+        #[template_callback]
+        fn on_search_previous(&self, _: gtk::Widget) {
+            if let Some(controller) = self.webview.borrow().find_controller() {
+                controller.search_previous();
+            }
+        }
+        #[template_callback]
+        fn on_stop_search(&self, _: gtk::SearchEntry) {
+            self.search_bar.set_search_mode(false);
+        }
+        #[template_callback]
+        fn on_search_mode_changed(&self, _: glib::ParamSpec) {
+            if !self.search_bar.is_search_mode() {
+                if let Some(controller) = self.webview.borrow().find_controller() {
+                    controller.search_finish();
+                }
+                self.match_label.set_label("");
+                self.webview.borrow().grab_focus();
+            }
+        }
     }
 }
 
@@ -378,6 +913,7 @@ impl AppWindow {
         let obj: Self = glib::Object::builder()
             .property("application", application)
             .build();
+        obj.setup_help_overlay();
         obj.imp().set_details(details);
         obj
     }
@@ -386,6 +922,25 @@ impl AppWindow {
         self.imp().details.borrow().id.clone()
     }
 
+    /// Asks about or applies the app's remembered permissions, popups go through here too
+    pub fn handle_permission_request(
+        &self,
+        webview: &WebView,
+        request: &webkit::PermissionRequest,
+    ) {
+        self.imp().handle_permission_request(webview, request);
+    }
+
+    /// Focuses the window and lets the page know if the notification came from it
+    pub fn notification_clicked(&self, id: u64) -> bool {
+        let Some(notification) = self.imp().notifications.borrow_mut().remove(&id) else {
+            return false;
+        };
+        notification.clicked();
+        self.present();
+        true
+    }
+
     fn setup_gactions(&self) {
         self.add_action_entries([
             gio::ActionEntry::builder("forward")
@@ -394,8 +949,61 @@ impl AppWindow {
             gio::ActionEntry::builder("back")
                 .activate(move |win: &Self, _, _| win.imp().go_back())
                 .build(),
+            gio::ActionEntry::builder("home")
+                .activate(move |win: &Self, _, _| win.imp().go_home())
+                .build(),
+            gio::ActionEntry::builder("reload")
+                .activate(move |win: &Self, _, _| win.imp().reload(false))
+                .build(),
+            gio::ActionEntry::builder("reload-bypass-cache")
+                .activate(move |win: &Self, _, _| win.imp().reload(true))
+                .build(),
+            gio::ActionEntry::builder("stop")
+                .activate(move |win: &Self, _, _| win.imp().stop())
+                .build(),
+            gio::ActionEntry::builder("zoom-in")
+                .activate(move |win: &Self, _, _| win.imp().zoom(1))
+                .build(),
+            gio::ActionEntry::builder("zoom-out")
+                .activate(move |win: &Self, _, _| win.imp().zoom(-1))
+                .build(),
+            gio::ActionEntry::builder("zoom-reset")
+                .activate(move |win: &Self, _, _| win.imp().zoom(0))
+                .build(),
+            gio::ActionEntry::builder("copy-url")
+                .activate(move |win: &Self, _, _| win.imp().copy_url())
+                .build(),
+            gio::ActionEntry::builder("print")
+                .activate(move |win: &Self, _, _| win.imp().print())
+                .build(),
+            gio::ActionEntry::builder("fullscreen")
+                .activate(move |win: &Self, _, _| win.imp().toggle_fullscreen())
+                .build(),
+            gio::ActionEntry::builder("inspector")
+                .activate(move |win: &Self, _, _| win.imp().show_inspector())
+                .build(),
+            gio::ActionEntry::builder("find")
+                .activate(move |win: &Self, _, _| win.imp().find())
+                .build(),
+            gio::ActionEntry::builder("switch-profile")
+                .parameter_type(Some(&String::static_variant_type()))
+                .state(DEFAULT_PROFILE.to_variant())
+                .activate(move |win: &Self, _, param| {
+                    if let Some(profile) = param.and_then(|x| x.get::<String>()) {
+                        win.imp().switch_profile(&profile);
+                    }
+                })
+                .build(),
         ]);
     }
+    // The application gives every window the main window's shortcuts when it's added,
+    // so this has to be replaced after the application is set
+    fn setup_help_overlay(&self) {
+        let builder =
+            gtk::Builder::from_resource("/io/github/zaedus/spider/app_window_shortcuts.ui");
+        let shortcuts: gtk::ShortcutsWindow = builder.object("app_window_shortcuts").unwrap();
+        self.set_help_overlay(Some(&shortcuts));
+    }
     fn setup_gestures(&self) {
         let gesture = gtk::GestureClick::new();
         gesture.set_button(0);
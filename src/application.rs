@@ -6,11 +6,12 @@ use anyhow::anyhow;
 use gtk::{gio, glib};
 
 use crate::app_window::AppWindow;
-use crate::apps::clean_app_dirs;
 use crate::apps::get_app_details;
-use crate::apps::AppsSettings;
+use crate::apps::{app_store, claim_app_bus_name, clean_app_dirs};
+use crate::cli;
 use crate::config;
 use crate::config::APP_ID;
+use crate::store;
 use crate::SpiderWindow;
 use glib::{OptionArg, OptionFlags};
 
@@ -18,6 +19,12 @@ pub fn settings() -> gio::Settings {
     gio::Settings::new(config::APP_ID)
 }
 
+/// Runs a future to completion on the main context
+/// Used by the command line, which has to finish before it can return an exit code
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    glib::MainContext::default().block_on(future)
+}
+
 mod imp {
 
     use super::*;
@@ -45,9 +52,107 @@ mod imp {
                 "lists the known applications",
                 None,
             );
+            obj.add_main_option(
+                "json",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::None,
+                "lists the applications as JSON, use with --list-applications",
+                None,
+            );
+            obj.add_main_option(
+                "create",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::String,
+                "creates an application for the website",
+                Some("URL"),
+            );
+            obj.add_main_option(
+                "title",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::String,
+                "title of the created application, instead of the website's",
+                Some("TITLE"),
+            );
+            obj.add_main_option(
+                "delete",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::String,
+                "deletes the application",
+                Some("ID"),
+            );
+            obj.add_main_option(
+                "show",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::String,
+                "prints the settings of the application",
+                Some("ID"),
+            );
+            obj.add_main_option(
+                "edit",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::String,
+                "changes the settings given with --set of the application",
+                Some("ID"),
+            );
+            obj.add_main_option(
+                "set",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::StringArray,
+                "a setting to change as printed by --show, can be repeated",
+                Some("KEY=VALUE"),
+            );
+            obj.add_main_option(
+                "autostart",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::None,
+                "launches the applications that start on login",
+                None,
+            );
+            obj.add_main_option(
+                "background",
+                glib::Char::from(0),
+                OptionFlags::NONE,
+                OptionArg::None,
+                "starts the application without showing its window",
+                None,
+            );
             obj.set_accels_for_action("app.quit", &["<primary>q"]);
             obj.set_accels_for_action("win.back", &["<alt>Left", "Back"]);
             obj.set_accels_for_action("win.forward", &["<alt>Right", "Forward"]);
+            obj.set_accels_for_action("win.find", &["<primary>f"]);
+            obj.set_accels_for_action("win.reload", &["<primary>r", "F5", "Reload"]);
+            obj.set_accels_for_action(
+                "win.reload-bypass-cache",
+                &["<primary><shift>r", "<shift>F5"],
+            );
+            obj.set_accels_for_action("win.stop", &["Escape", "Stop"]);
+            obj.set_accels_for_action("win.home", &["<alt>Home", "HomePage"]);
+            obj.set_accels_for_action(
+                "win.zoom-in",
+                &[
+                    "<primary>plus",
+                    "<primary>equal",
+                    "<primary>KP_Add",
+                    "ZoomIn",
+                ],
+            );
+            obj.set_accels_for_action(
+                "win.zoom-out",
+                &["<primary>minus", "<primary>KP_Subtract", "ZoomOut"],
+            );
+            obj.set_accels_for_action("win.zoom-reset", &["<primary>0", "<primary>KP_0"]);
+            obj.set_accels_for_action("win.copy-url", &["<primary><shift>l"]);
+            obj.set_accels_for_action("win.print", &["<primary>p"]);
+            obj.set_accels_for_action("win.fullscreen", &["F11"]);
+            obj.set_accels_for_action("win.inspector", &["F12", "<primary><shift>i"]);
         }
     }
 
@@ -58,17 +163,88 @@ mod imp {
             // Clean up
             clean_app_dirs().unwrap();
 
+            let options = command_line.options_dict();
+
+            // Options that only modify another one would otherwise be silently ignored
+            for (option, required) in [
+                ("json", "list-applications"),
+                ("title", "create"),
+                ("set", "edit"),
+            ] {
+                if options.contains(option) && !options.contains(required) {
+                    eprintln!("Error: --{option} can only be used with --{required}");
+                    return glib::ExitCode::FAILURE;
+                }
+            }
+
             // If listing application via the command line
-            if command_line
-                .options_dict()
+            if options
                 .lookup::<bool>("list-applications")
                 .unwrap_or(None)
                 .unwrap_or(false)
             {
-                let apps_settings = settings().get::<AppsSettings>("apps-settings");
-                for id in settings().get::<Vec<String>>("app-ids") {
-                    if let Some(title) = apps_settings.get(&id).and_then(|x| x.get("title")) {
-                        println!("{id}\t{title}");
+                if options
+                    .lookup::<bool>("json")
+                    .unwrap_or(None)
+                    .unwrap_or(false)
+                {
+                    if let Err(err) = block_on(cli::list_applications_json()) {
+                        eprintln!("Error: {err}");
+                        return glib::ExitCode::FAILURE;
+                    }
+                } else {
+                    cli::list_applications();
+                }
+                return glib::ExitCode::SUCCESS;
+            }
+
+            // If managing applications via the command line
+            let string_option = |name: &str| options.lookup::<String>(name).unwrap_or(None);
+            let result = if let Some(url) = string_option("create") {
+                Some(
+                    block_on(cli::create_app(&url, string_option("title"))).map(|id| {
+                        println!("{id}");
+                    }),
+                )
+            } else if let Some(id) = string_option("delete") {
+                Some(block_on(cli::delete_app(&id)))
+            } else if let Some(id) = string_option("show") {
+                Some(cli::show_app(&id))
+            } else if let Some(id) = string_option("edit") {
+                let assignments = options
+                    .lookup::<Vec<String>>("set")
+                    .unwrap_or(None)
+                    .unwrap_or_default();
+                Some(block_on(cli::edit_app(&id, &assignments)))
+            } else {
+                None
+            };
+            if let Some(result) = result {
+                return match result {
+                    Ok(_) => glib::ExitCode::SUCCESS,
+                    Err(err) => {
+                        eprintln!("Error: {err}");
+                        glib::ExitCode::FAILURE
+                    }
+                };
+            }
+
+            // If launching the applications that start on login
+            if options
+                .lookup::<bool>("autostart")
+                .unwrap_or(None)
+                .unwrap_or(false)
+            {
+                for details in settings()
+                    .get::<Vec<String>>("app-ids")
+                    .iter()
+                    .filter_map(|id| get_app_details(id))
+                    .filter(|details| details.autostart)
+                {
+                    if let Err(err) = application
+                        .open_app_with_visibility(&details.id, !details.run_in_background)
+                    {
+                        eprintln!("Failed to open app {}: {err}", details.id);
                     }
                 }
                 return glib::ExitCode::SUCCESS;
@@ -79,7 +255,21 @@ mod imp {
                 match get_app_details(&id.to_string_lossy())
                     .ok_or(anyhow!("No app with id {:?}", id))
                 {
-                    Ok(details) => AppWindow::new(&self.obj().clone(), &details).upcast(),
+                    Ok(details) => {
+                        // Apps running in the background are unique, so reuse their window
+                        application
+                            .windows()
+                            .into_iter()
+                            .find(|x| {
+                                x.downcast_ref::<AppWindow>()
+                                    .is_some_and(|x| x.id() == details.id)
+                            })
+                            .unwrap_or_else(|| {
+                                // Lets the manager tell that the app is open
+                                claim_app_bus_name(&details.id);
+                                AppWindow::new(&self.obj().clone(), &details).upcast()
+                            })
+                    }
                     Err(err) => {
                         eprintln!("Error: {err}");
                         return glib::ExitCode::FAILURE;
@@ -88,7 +278,13 @@ mod imp {
             } else {
                 SpiderWindow::new(&*application).upcast()
             };
-            window.present();
+            if !options
+                .lookup::<bool>("background")
+                .unwrap_or(None)
+                .unwrap_or(false)
+            {
+                window.present();
+            }
 
             glib::ExitCode::SUCCESS
         }
@@ -106,20 +302,38 @@ glib::wrapper! {
 
 impl SpiderApplication {
     pub fn new(flags: &gio::ApplicationFlags) -> Self {
+        // Has to happen before any settings of the apps are read, the cleanup on
+        // launch also goes by the apps it can find
+        store::migrate(&app_store());
+
+        // Apps that run in the background need to be unique so launching them
+        // again brings back their hidden window instead of starting another one
+        let mut flags = *flags;
+        if Self::launched_app_id()
+            .and_then(|id| get_app_details(&id))
+            .is_some_and(|details| details.run_in_background)
+        {
+            flags.remove(gio::ApplicationFlags::NON_UNIQUE);
+        }
+
         glib::Object::builder()
             .property("flags", flags)
             .property("application-id", Self::gen_app_id())
             .build()
     }
 
-    fn gen_app_id() -> String {
-        let args: Vec<String> = std::env::args().take(2).collect();
+    fn launched_app_id() -> Option<String> {
+        let id = std::env::args().nth(1)?;
+        settings()
+            .get::<Vec<String>>("app-ids")
+            .contains(&id)
+            .then_some(id)
+    }
 
-        if let Some(id) = args.get(1) {
-            if settings().get::<Vec<String>>("app-ids").contains(id) {
-                return format!("{}.{}", APP_ID, id);
-            }
-        };
+    fn gen_app_id() -> String {
+        if let Some(id) = Self::launched_app_id() {
+            return format!("{}.{}", APP_ID, id);
+        }
 
         APP_ID.to_string()
     }
@@ -144,9 +358,51 @@ impl SpiderApplication {
                     }
                 })
                 .build(),
+            gio::ActionEntry::builder("notification-clicked")
+                .parameter_type(Some(&u64::static_variant_type()))
+                .activate(move |app: &Self, _, id| {
+                    let id = id
+                        .expect("no id provided")
+                        .get::<u64>()
+                        .expect("invalid id type provided");
+                    app.notification_clicked(id);
+                })
+                .build(),
+            gio::ActionEntry::builder("open-file")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(move |_: &Self, _, path| {
+                    let path = path
+                        .expect("no path provided")
+                        .get::<String>()
+                        .expect("invalid path type provided");
+                    gtk::FileLauncher::new(Some(&gio::File::for_path(path))).launch(
+                        None::<&gtk::Window>,
+                        None::<&gio::Cancellable>,
+                        |result| {
+                            if let Err(err) = result {
+                                eprintln!("Failed to open file: {err}");
+                            }
+                        },
+                    );
+                })
+                .build(),
         ]);
     }
 
+    fn notification_clicked(&self, id: u64) {
+        for window in self.windows() {
+            if let Some(window) = window.downcast_ref::<AppWindow>() {
+                if window.notification_clicked(id) {
+                    return;
+                }
+            }
+        }
+        // The notification may have been replaced, still bring the app up
+        if let Some(window) = self.active_window() {
+            window.present();
+        }
+    }
+
     fn show_about(&self) {
         let window = self.active_window().unwrap();
         let about = adw::AboutDialog::builder()
@@ -164,7 +420,16 @@ impl SpiderApplication {
     }
 
     fn open_app(&self, id: &str) -> anyhow::Result<()> {
-        Command::new("spider").arg(id).spawn()?;
+        self.open_app_with_visibility(id, true)
+    }
+
+    fn open_app_with_visibility(&self, id: &str, visible: bool) -> anyhow::Result<()> {
+        let mut command = Command::new("spider");
+        command.arg(id);
+        if !visible {
+            command.arg("--background");
+        }
+        command.spawn()?;
         Ok(())
     }
 }
@@ -1,11 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use ashpd::{
     desktop::{
+        background::Background,
         dynamic_launcher::{DynamicLauncherProxy, LauncherType, PrepareInstallOptions},
         Icon,
     },
@@ -13,10 +14,20 @@ use ashpd::{
 };
 use dircpy::copy_dir;
 use gtk::prelude::SettingsExtManual;
-use gtk::{gdk, glib};
+use gtk::{gdk, gio, glib};
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 
-use crate::{application::settings, config, util::to_gdk_texture};
+use crate::{
+    application::settings,
+    config,
+    injections::{self, Injection},
+    permissions::{self, Permission},
+    profiles::{self, Profile, DEFAULT_PROFILE},
+    scope,
+    store::{AppStore, FileStore, GSettingsStore},
+    util::{dir_size, to_gdk_texture},
+};
 
 pub type AppsSettings = HashMap<String, HashMap<String, String>>;
 
@@ -25,17 +36,62 @@ lazy_static! {
     static ref cache_dir: PathBuf = glib::user_cache_dir().join(glib::application_name().unwrap());
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyMode {
+    #[default]
+    System,
+    None,
+    Custom {
+        url: String,
+        ignore_hosts: Vec<String>,
+    },
+}
+
+impl ProxyMode {
+    /// Checks that WebKit can use a custom proxy, it ignores urls it doesn't understand
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let ProxyMode::Custom { url, .. } = self else {
+            return Ok(());
+        };
+        let url = url::Url::parse(url).map_err(|err| anyhow!("Invalid proxy url: {err}"))?;
+        if !["http", "https", "socks", "socks5"].contains(&url.scheme()) || !url.has_host() {
+            bail!("Proxy url must look like http://, https://, socks:// or socks5://host:port");
+        }
+        Ok(())
+    }
+}
+
+/// Fields missing from an app's settings file take their default, so adding one needs no migration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppDetails {
     pub id: String,
     pub url: String,
     pub title: String,
+    #[serde(skip)]
     pub icon: Option<Vec<u8>>,
     pub has_titlebar_color: bool,
     pub window_width: i32,
     pub window_height: i32,
     pub window_maximize: bool,
+    pub zoom_level: f64,
     pub user_agent: Option<String>,
+    pub scope: Vec<String>,
+    pub permissions: HashMap<Permission, bool>,
+    pub mute_notifications: bool,
+    pub run_in_background: bool,
+    pub autostart: bool,
+    pub proxy: ProxyMode,
+    pub theme_color: Option<String>,
+    /// Profiles besides the default one, which always exists
+    pub profiles: Vec<Profile>,
+    /// Id of the profile the app opens with
+    pub profile: String,
+    /// Downloads are saved here without asking when set
+    pub download_dir: Option<String>,
+    pub injections: Vec<Injection>,
+    pub content_blocking: bool,
 }
 
 impl PartialEq for AppDetails {
@@ -46,6 +102,18 @@ impl PartialEq for AppDetails {
             && self.icon == other.icon
             && self.has_titlebar_color == other.has_titlebar_color
             && self.user_agent == other.user_agent
+            && self.scope == other.scope
+            && self.permissions == other.permissions
+            && self.mute_notifications == other.mute_notifications
+            && self.run_in_background == other.run_in_background
+            && self.autostart == other.autostart
+            && self.proxy == other.proxy
+            && self.theme_color == other.theme_color
+            && self.profiles == other.profiles
+            && self.profile == other.profile
+            && self.download_dir == other.download_dir
+            && self.injections == other.injections
+            && self.content_blocking == other.content_blocking
     }
 }
 
@@ -60,7 +128,20 @@ impl Default for AppDetails {
             window_width: 400,
             window_height: 400,
             window_maximize: false,
+            zoom_level: 1.0,
             user_agent: None,
+            scope: Vec::new(),
+            permissions: HashMap::new(),
+            mute_notifications: false,
+            run_in_background: false,
+            autostart: false,
+            proxy: ProxyMode::System,
+            theme_color: None,
+            profiles: Vec::new(),
+            profile: DEFAULT_PROFILE.to_string(),
+            download_dir: None,
+            injections: Vec::new(),
+            content_blocking: false,
         }
     }
 }
@@ -69,6 +150,7 @@ impl AppDetails {
     pub fn new(id: String, title: String, url: String) -> Self {
         Self {
             id,
+            scope: scope::default_scope(&url),
             url,
             title,
             ..Default::default()
@@ -88,13 +170,166 @@ impl AppDetails {
                 "windowmaximize".to_string(),
                 self.window_maximize.to_string(),
             ),
+            ("zoomlevel".to_string(), self.zoom_level.to_string()),
+            (
+                "mutenotifications".to_string(),
+                self.mute_notifications.to_string(),
+            ),
+            (
+                "runinbackground".to_string(),
+                self.run_in_background.to_string(),
+            ),
+            ("autostart".to_string(), self.autostart.to_string()),
+            (
+                "contentblocking".to_string(),
+                self.content_blocking.to_string(),
+            ),
         ];
         if let Some(user_agent) = &self.user_agent {
             kv_pairs.push(("useragent".to_string(), user_agent.clone()));
         }
+        if !self.scope.is_empty() {
+            kv_pairs.push(("scope".to_string(), self.scope.join(",")));
+        }
+        if let Some(theme_color) = &self.theme_color {
+            kv_pairs.push(("themecolor".to_string(), theme_color.clone()));
+        }
+        if let Some(download_dir) = &self.download_dir {
+            kv_pairs.push(("downloaddir".to_string(), download_dir.clone()));
+        }
+        if !self.injections.is_empty() {
+            kv_pairs.push((
+                "injections".to_string(),
+                injections::injections_to_string(&self.injections),
+            ));
+        }
+        if !self.profiles.is_empty() {
+            kv_pairs.push((
+                "profiles".to_string(),
+                profiles::profiles_to_string(&self.profiles),
+            ));
+        }
+        if self.profile != DEFAULT_PROFILE {
+            kv_pairs.push(("profile".to_string(), self.profile.clone()));
+        }
+        match &self.proxy {
+            ProxyMode::System => (),
+            ProxyMode::None => kv_pairs.push(("proxymode".to_string(), "none".to_string())),
+            ProxyMode::Custom { url, ignore_hosts } => {
+                kv_pairs.push(("proxymode".to_string(), "custom".to_string()));
+                kv_pairs.push(("proxyurl".to_string(), url.clone()));
+                kv_pairs.push(("proxyignorehosts".to_string(), ignore_hosts.join(",")));
+            }
+        }
+        for (permission, allowed) in &self.permissions {
+            kv_pairs.push((permission.settings_key(), allowed.to_string()));
+        }
 
         kv_pairs.into_iter().collect()
     }
+    /// Every profile of the app, starting with the default one
+    pub fn all_profiles(&self) -> Vec<Profile> {
+        let mut profiles = vec![Profile::default_profile()];
+        profiles.extend(self.profiles.iter().cloned());
+        profiles
+    }
+    /// The host patterns that navigation is restricted to, falling back to the
+    /// host of the app's url if none were set
+    pub fn allowed_hosts(&self) -> Vec<String> {
+        if self.scope.is_empty() {
+            scope::default_scope(&self.url)
+        } else {
+            self.scope.clone()
+        }
+    }
+    pub fn from_hashmap(id: &str, settings: &HashMap<String, String>) -> Option<Self> {
+        let url = settings.get("url")?.to_string();
+        let profiles = settings
+            .get("profiles")
+            .map(|x| profiles::parse_profiles(x))
+            .unwrap_or_default();
+        // Fall back to the default profile if the selected one was removed
+        let profile = settings
+            .get("profile")
+            .filter(|x| profiles.iter().any(|profile| &&profile.id == x))
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        Some(AppDetails {
+            id: id.to_string(),
+            title: settings.get("title")?.to_string(),
+            has_titlebar_color: settings
+                .get("hastitlebarcolor")
+                .is_none_or(|x| x != "false"),
+            icon: None,
+            window_width: settings
+                .get("windowwidth")
+                .and_then(|x| x.parse::<i32>().ok())
+                .unwrap_or(400),
+            window_height: settings
+                .get("windowheight")
+                .and_then(|x| x.parse::<i32>().ok())
+                .unwrap_or(400),
+            window_maximize: settings
+                .get("windowmaximize")
+                .and_then(|x| x.parse::<bool>().ok())
+                .unwrap_or(false),
+            zoom_level: settings
+                .get("zoomlevel")
+                .and_then(|x| x.parse::<f64>().ok())
+                .filter(|x| *x > 0.0)
+                .unwrap_or(1.0),
+            user_agent: settings.get("useragent").map(|x| x.to_string()),
+            scope: settings
+                .get("scope")
+                .map(|x| scope::parse_scope(x))
+                .unwrap_or_else(|| scope::default_scope(&url)),
+            permissions: Permission::ALL
+                .into_iter()
+                .filter_map(|permission| {
+                    settings
+                        .get(&permission.settings_key())
+                        .and_then(|x| permissions::parse_decision(x))
+                        .map(|allowed| (permission, allowed))
+                })
+                .collect(),
+            mute_notifications: settings
+                .get("mutenotifications")
+                .and_then(|x| x.parse::<bool>().ok())
+                .unwrap_or(false),
+            run_in_background: settings
+                .get("runinbackground")
+                .and_then(|x| x.parse::<bool>().ok())
+                .unwrap_or(false),
+            autostart: settings
+                .get("autostart")
+                .and_then(|x| x.parse::<bool>().ok())
+                .unwrap_or(false),
+            proxy: match settings.get("proxymode").map(|x| x.as_str()) {
+                Some("none") => ProxyMode::None,
+                Some("custom") => ProxyMode::Custom {
+                    url: settings.get("proxyurl").cloned().unwrap_or_default(),
+                    ignore_hosts: settings
+                        .get("proxyignorehosts")
+                        .map(|x| scope::parse_scope(x))
+                        .unwrap_or_default(),
+                },
+                _ => ProxyMode::System,
+            },
+            theme_color: settings.get("themecolor").map(|x| x.to_string()),
+            profiles,
+            profile,
+            download_dir: settings.get("downloaddir").map(|x| x.to_string()),
+            injections: settings
+                .get("injections")
+                .and_then(|x| injections::parse_injections(x).ok())
+                .unwrap_or_default(),
+            content_blocking: settings
+                .get("contentblocking")
+                .and_then(|x| x.parse::<bool>().ok())
+                .unwrap_or(false),
+            url,
+        })
+    }
     pub fn with_icon(self, icon: Vec<u8>) -> Self {
         AppDetails {
             icon: Some(icon),
@@ -105,22 +340,123 @@ impl AppDetails {
         to_gdk_texture(self.icon.clone().unwrap().as_slice(), size)
     }
     pub fn save(&self) -> anyhow::Result<()> {
+        app_store().save(self)?;
+
         let settings = settings();
         let mut apps = settings.get::<Vec<String>>("app-ids");
         if !apps.contains(&self.id) {
             apps.push(self.id.clone());
+            settings.set("app-ids", apps)?;
         }
 
-        let mut apps_settings = settings.get::<AppsSettings>("apps-settings");
-        apps_settings.insert(self.id.clone(), self.to_hashmap());
+        Ok(())
+    }
+}
 
-        settings.set("app-ids", apps)?;
-        settings.set("apps-settings", apps_settings)?;
+/// Directory holding the app's WebKit data and cookies
+pub fn app_data_dir(id: &str) -> PathBuf {
+    data_dir.join(id)
+}
 
-        Ok(())
+pub fn app_cache_dir(id: &str) -> PathBuf {
+    cache_dir.join(id)
+}
+
+pub fn app_store() -> FileStore {
+    FileStore::new(data_dir.to_path_buf())
+}
+
+/// Disk space used by an app, the cache is kept apart since it can be trimmed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AppSize {
+    pub data: u64,
+    pub cache: u64,
+}
+
+impl AppSize {
+    pub fn total(&self) -> u64 {
+        self.data + self.cache
     }
 }
 
+/// Walks the app's directories on a worker thread, profiles can grow large
+pub async fn get_app_size(id: &str) -> AppSize {
+    let data_path = app_data_dir(id);
+    let cache_path = app_cache_dir(id);
+    gio::spawn_blocking(move || AppSize {
+        data: dir_size(&data_path),
+        cache: dir_size(&cache_path),
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Empties the app's cache directory for every profile, logins are kept in the data directory
+/// Compiled content filters are cached there too and get compiled again on the next launch
+pub async fn trim_app_cache(id: &str) -> anyhow::Result<()> {
+    // An open app keeps using its cache, pulling it out from under WebKit breaks pages
+    if is_app_running(id).await {
+        bail!("Close the app before trimming its cache");
+    }
+    let path = app_cache_dir(id);
+    gio::spawn_blocking(move || -> anyhow::Result<()> {
+        let Ok(entries) = std::fs::read_dir(&path) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(entry.path())?;
+            } else {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|_| anyhow!("Failed to trim the cache"))?
+}
+
+/// Name the app's process holds on the session bus while it runs
+/// Kept apart from the application id so it never stands in for a unique instance,
+/// whether or not the app runs in the background
+fn app_bus_name(id: &str) -> String {
+    format!("{}.{}.Running", config::APP_ID, id)
+}
+
+/// Takes the app's name on the session bus for as long as the process runs
+pub fn claim_app_bus_name(id: &str) {
+    gio::bus_own_name(
+        gio::BusType::Session,
+        &app_bus_name(id),
+        gio::BusNameOwnerFlags::NONE,
+        |_, _| (),
+        |_, _| (),
+        |_, _| (),
+    );
+}
+
+/// Whether the app is open in another process, its WebKit data is in use then
+pub async fn is_app_running(id: &str) -> bool {
+    let Ok(connection) = gio::bus_get_future(gio::BusType::Session).await else {
+        return false;
+    };
+    connection
+        .call_future(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+            Some(&glib::ToVariant::to_variant(&(app_bus_name(id),))),
+            glib::VariantTy::new("(b)").ok(),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .ok()
+        .and_then(|x| x.get::<(bool,)>())
+        .is_some_and(|(running,)| running)
+}
+
 #[inline]
 fn id_to_desktop(id: &str) -> String {
     format!("{}.{}.desktop", config::APP_ID, id)
@@ -132,17 +468,21 @@ pub fn delete_app_details(id: &str) -> anyhow::Result<()> {
     if let Some(idx) = apps.iter().position(|x| x == id) {
         apps.remove(idx);
     }
-    let mut apps_settings = settings.get::<AppsSettings>("apps-settings");
-    if apps_settings.contains_key(id) {
-        apps_settings.remove(id);
-    }
-
     settings.set("app-ids", apps)?;
-    settings.set("apps-settings", apps_settings)?;
+    app_store().delete(id)?;
 
     Ok(())
 }
 
+/// Whether the app's desktop file is still known to the launcher portal
+pub async fn is_app_installed(id: &str) -> anyhow::Result<bool> {
+    let proxy = DynamicLauncherProxy::new().await?;
+    Ok(proxy
+        .desktop_entry(id_to_desktop(id).as_str())
+        .await
+        .is_ok())
+}
+
 pub async fn get_app_icon(id: &str) -> anyhow::Result<Vec<u8>> {
     let desktop_id = id_to_desktop(id);
     let proxy = DynamicLauncherProxy::new().await?;
@@ -153,39 +493,20 @@ pub async fn get_app_icon(id: &str) -> anyhow::Result<Vec<u8>> {
 }
 
 pub fn get_app_details(id: &str) -> Option<AppDetails> {
-    let settings = settings();
-    let settings = settings.get::<AppsSettings>("apps-settings");
-    let settings = settings.get(id)?;
-    Some(AppDetails {
-        id: id.to_string(),
-        url: settings.get("url").unwrap().to_string(),
-        title: settings.get("title").unwrap().to_string(),
-        has_titlebar_color: settings
-            .get("hastitlebarcolor")
-            .is_none_or(|x| x != "false"),
-        icon: None,
-        window_width: settings
-            .get("windowwidth")
-            .and_then(|x| x.parse::<i32>().ok())
-            .unwrap_or(400),
-        window_height: settings
-            .get("windowheight")
-            .and_then(|x| x.parse::<i32>().ok())
-            .unwrap_or(400),
-        window_maximize: settings
-            .get("windowmaximize")
-            .and_then(|x| x.parse::<bool>().ok())
-            .unwrap_or(false),
-        user_agent: settings.get("useragent").map(|x| x.to_string()),
-    })
+    app_store()
+        .load(id)
+        // Apps that failed to migrate are still in the old key until the next attempt
+        .or_else(|_| GSettingsStore.load(id))
+        .ok()
 }
 
 pub async fn uninstall_app(id: &str) -> anyhow::Result<()> {
     let proxy = DynamicLauncherProxy::new().await?;
+    let had_autostart = get_app_details(id).is_some_and(|x| x.autostart);
 
     proxy.uninstall(&id_to_desktop(id)).await?;
-    let app_data_dir = data_dir.join(id);
-    let app_cache_dir = cache_dir.join(id);
+    let app_data_dir = app_data_dir(id);
+    let app_cache_dir = app_cache_dir(id);
     if app_data_dir.exists() {
         std::fs::remove_dir_all(app_data_dir)?;
     }
@@ -193,6 +514,32 @@ pub async fn uninstall_app(id: &str) -> anyhow::Result<()> {
         std::fs::remove_dir_all(app_cache_dir)?;
     }
     delete_app_details(id)?;
+    if had_autostart {
+        update_autostart(None).await?;
+    }
+
+    Ok(())
+}
+
+/// Syncs the autostart entry with the apps that should start on login
+/// The background portal only allows a single entry for Spider, so it runs
+/// `spider --autostart` which then launches every app that asked for it
+pub async fn update_autostart(wid: Option<WindowIdentifier>) -> anyhow::Result<()> {
+    let autostart = settings()
+        .get::<Vec<String>>("app-ids")
+        .iter()
+        .filter_map(|id| get_app_details(id))
+        .any(|details| details.autostart);
+
+    Background::request()
+        .identifier(wid)
+        .reason("Start web apps when you log in")
+        .auto_start(autostart)
+        .dbus_activatable(false)
+        .command(&["spider", "--autostart"])
+        .send()
+        .await?
+        .response()?;
 
     Ok(())
 }
@@ -275,15 +622,26 @@ pub fn clean_app_dirs() -> anyhow::Result<()> {
 }
 
 pub fn copy_app_dir(old_id: &str, new_id: &str) -> anyhow::Result<()> {
-    for folder in [data_dir.to_path_buf(), cache_dir.to_path_buf()] {
-        if !folder.exists() {
+    copy_dir_contents(&app_data_dir(old_id), &app_data_dir(new_id), &[])?;
+    copy_dir_contents(&app_cache_dir(old_id), &app_cache_dir(new_id), &[])?;
+    Ok(())
+}
+
+/// Copies what's in a directory, leaving out the items named in `skip` at its top level
+/// Missing directories are left alone, apps that never ran have no cache yet
+pub fn copy_dir_contents(from: &Path, to: &Path, skip: &[&str]) -> anyhow::Result<()> {
+    if !from.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(to)?;
+    for item in std::fs::read_dir(from)?.flatten() {
+        if skip.contains(&item.file_name().to_string_lossy().as_ref()) {
             continue;
         }
-        for item in std::fs::read_dir(folder.clone()).unwrap().flatten() {
-            if item.file_type().unwrap().is_dir() && item.file_name().to_string_lossy() == old_id {
-                copy_dir(folder.join(old_id), folder.join(new_id))?;
-                break;
-            }
+        if item.file_type()?.is_dir() {
+            copy_dir(item.path(), to.join(item.file_name()))?;
+        } else {
+            std::fs::copy(item.path(), to.join(item.file_name()))?;
         }
     }
     Ok(())
@@ -0,0 +1,214 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use dircpy::copy_dir;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use gtk::{gio, glib};
+use serde::{Deserialize, Serialize};
+
+use crate::apps::{app_data_dir, get_app_details, get_app_icon, AppDetails};
+use crate::injections::injections_dir;
+use crate::store;
+
+/// Bump whenever the layout of the bundle changes in an incompatible way
+const BUNDLE_VERSION: u32 = 2;
+const MANIFEST_PATH: &str = "manifest.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    apps: Vec<ManifestApp>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestApp {
+    id: String,
+    /// The app's settings as they are stored in its settings file, with their own version
+    settings: serde_json::Value,
+    #[serde(default)]
+    has_data: bool,
+    /// Custom styles and scripts, which are part of the data when that is included
+    #[serde(default)]
+    has_injections: bool,
+}
+
+#[inline]
+fn icon_path(id: &str) -> String {
+    format!("icons/{id}.png")
+}
+
+#[inline]
+fn data_path(id: &str) -> String {
+    format!("data/{id}")
+}
+
+#[inline]
+fn injections_path(id: &str) -> String {
+    format!("injections/{id}")
+}
+
+#[derive(Debug)]
+pub struct BundleApp {
+    pub details: AppDetails,
+    pub icon: Vec<u8>,
+    /// Extracted WebKit data directory, if it was exported
+    pub data_dir: Option<PathBuf>,
+    pub injections_dir: Option<PathBuf>,
+}
+
+impl BundleApp {
+    /// Copies the exported WebKit data and custom styles and scripts to the app with the given id
+    pub fn restore_data(&self, id: &str) -> anyhow::Result<()> {
+        if let Some(data_dir) = &self.data_dir {
+            copy_dir(data_dir, app_data_dir(id))?;
+        }
+        if let Some(dir) = &self.injections_dir {
+            copy_dir(dir, injections_dir(id))?;
+        }
+        Ok(())
+    }
+}
+
+/// An extracted bundle, its files are removed once dropped
+#[derive(Debug)]
+pub struct Bundle {
+    dir: PathBuf,
+    pub apps: Vec<BundleApp>,
+}
+
+impl Drop for Bundle {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.dir) {
+            eprintln!("Failed to remove extracted bundle: {err}");
+        }
+    }
+}
+
+fn append_file<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    contents: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, path, contents)
+}
+
+fn write_bundle(
+    path: &Path,
+    apps: Vec<(AppDetails, Vec<u8>)>,
+    include_data: bool,
+) -> anyhow::Result<()> {
+    let file = File::create(path)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut manifest = Manifest {
+        version: BUNDLE_VERSION,
+        apps: Vec::new(),
+    };
+
+    for (details, icon) in apps {
+        append_file(&mut archive, &icon_path(&details.id), &icon)?;
+
+        let app_data_dir = app_data_dir(&details.id);
+        let has_data = include_data && app_data_dir.exists();
+        if has_data {
+            archive.append_dir_all(data_path(&details.id), app_data_dir)?;
+        }
+        let injections_dir = injections_dir(&details.id);
+        let has_injections = !has_data && injections_dir.exists();
+        if has_injections {
+            archive.append_dir_all(injections_path(&details.id), injections_dir)?;
+        }
+
+        manifest.apps.push(ManifestApp {
+            id: details.id.clone(),
+            settings: store::to_document(&details)?,
+            has_data,
+            has_injections,
+        });
+    }
+    append_file(
+        &mut archive,
+        MANIFEST_PATH,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    archive.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+fn read_bundle(path: &Path) -> anyhow::Result<Bundle> {
+    let dir = glib::mkdtemp(glib::tmp_dir().join("spider-import-XXXXXX"))
+        .ok_or(anyhow!("Failed to create a temporary directory"))?;
+    // Constructed early so the directory is cleaned up on every error below
+    let mut bundle = Bundle {
+        dir,
+        apps: Vec::new(),
+    };
+
+    tar::Archive::new(GzDecoder::new(File::open(path)?)).unpack(&bundle.dir)?;
+
+    let manifest: Manifest =
+        serde_json::from_slice(&std::fs::read(bundle.dir.join(MANIFEST_PATH))?)
+            .map_err(|_| anyhow!("Not a Spider bundle"))?;
+    if manifest.version > BUNDLE_VERSION {
+        bail!("Bundle was made by a newer version of Spider");
+    }
+    // Settings used to be exported as strings, which dropped anything not listed by hand
+    if manifest.version < BUNDLE_VERSION {
+        bail!("Bundle was made by an older version of Spider, export the apps again");
+    }
+
+    for app in manifest.apps {
+        // Ids end up in paths, so don't let them escape the bundle
+        if app.id.is_empty() || app.id.contains(['/', '.']) {
+            bail!("Bundle has an invalid app id {:?}", app.id);
+        }
+        let details = store::from_document(&app.id, app.settings)
+            .map_err(|err| anyhow!("Bundle has an invalid app {}: {err}", app.id))?;
+        let icon = std::fs::read(bundle.dir.join(icon_path(&app.id)))?;
+        let data_dir = app
+            .has_data
+            .then(|| bundle.dir.join(data_path(&app.id)))
+            .filter(|x| x.exists());
+        let injections_dir = app
+            .has_injections
+            .then(|| bundle.dir.join(injections_path(&app.id)))
+            .filter(|x| x.exists());
+        bundle.apps.push(BundleApp {
+            details,
+            icon,
+            data_dir,
+            injections_dir,
+        });
+    }
+
+    Ok(bundle)
+}
+
+/// Writes the given apps, their icons and optionally their WebKit data to an archive
+pub async fn export_apps(ids: &[String], path: PathBuf, include_data: bool) -> anyhow::Result<()> {
+    let mut apps = Vec::new();
+    for id in ids {
+        let details = get_app_details(id).ok_or(anyhow!("No app with id {id}"))?;
+        let icon = get_app_icon(id).await?;
+        apps.push((details, icon));
+    }
+
+    gio::spawn_blocking(move || write_bundle(&path, apps, include_data))
+        .await
+        .map_err(|_| anyhow!("Failed to write bundle"))?
+}
+
+/// Extracts an archive made by `export_apps`
+pub async fn import_bundle(path: PathBuf) -> anyhow::Result<Bundle> {
+    gio::spawn_blocking(move || read_bundle(&path))
+        .await
+        .map_err(|_| anyhow!("Failed to read bundle"))?
+}
@@ -0,0 +1,241 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::clone;
+use gtk::glib;
+use std::cell::RefCell;
+use webkit::WebsiteDataTypes;
+
+use crate::apps::{is_app_running, AppDetails};
+use crate::profiles;
+
+// Index of the time range in the combo row, the last one clears everything
+fn index_to_time_span(index: u32) -> glib::TimeSpan {
+    match index {
+        0 => glib::TimeSpan::from_hours(1),
+        1 => glib::TimeSpan::from_days(1),
+        2 => glib::TimeSpan::from_days(7),
+        3 => glib::TimeSpan::from_days(28),
+        _ => glib::TimeSpan(0),
+    }
+}
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/zaedus/spider/clear_data_dialog.ui")]
+    pub struct ClearDataDialog {
+        pub app_id: RefCell<String>,
+        // Only set up while the app is closed
+        pub data_manager: RefCell<Option<webkit::WebsiteDataManager>>,
+
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub clear_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub running_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub time_range_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub cookies_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub cookies_check: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub cache_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub cache_check: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub local_storage_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub local_storage_check: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub indexeddb_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub indexeddb_check: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub service_workers_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub service_workers_check: TemplateChild<gtk::CheckButton>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ClearDataDialog {
+        const NAME: &'static str = "ClearDataDialog";
+        type Type = super::ClearDataDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ClearDataDialog {}
+    impl WidgetImpl for ClearDataDialog {}
+    impl AdwDialogImpl for ClearDataDialog {}
+
+    #[gtk::template_callbacks]
+    impl ClearDataDialog {
+        #[template_callback]
+        fn on_cancel_clicked(&self, _: gtk::Button) {
+            self.obj().close();
+        }
+        #[template_callback]
+        fn validate_input_cb(&self, _: gtk::CheckButton) {
+            self.update_clear_button();
+        }
+        #[template_callback]
+        async fn on_clear_clicked(&self, _: gtk::Button) {
+            let Some(data_manager) = self.data_manager.borrow().clone() else {
+                return;
+            };
+            // The app may have been opened since the dialog was
+            let app_id = self.app_id.borrow().clone();
+            if is_app_running(&app_id).await {
+                self.set_running();
+                return;
+            }
+            self.clear_button.set_sensitive(false);
+            let result = data_manager
+                .clear_future(
+                    self.selected_types(),
+                    index_to_time_span(self.time_range_row.selected()),
+                )
+                .await;
+            self.update_clear_button();
+            match result {
+                Ok(_) => {
+                    self.toast_overlay
+                        .add_toast(adw::Toast::new("Browsing data cleared"));
+                    self.update_usage().await;
+                }
+                Err(err) => self
+                    .toast_overlay
+                    .add_toast(adw::Toast::new(err.to_string().as_str())),
+            }
+        }
+    }
+
+    impl ClearDataDialog {
+        fn categories(&self) -> [(WebsiteDataTypes, adw::ActionRow, gtk::CheckButton); 5] {
+            [
+                (
+                    WebsiteDataTypes::COOKIES,
+                    self.cookies_row.get(),
+                    self.cookies_check.get(),
+                ),
+                (
+                    WebsiteDataTypes::MEMORY_CACHE
+                        | WebsiteDataTypes::DISK_CACHE
+                        | WebsiteDataTypes::DOM_CACHE,
+                    self.cache_row.get(),
+                    self.cache_check.get(),
+                ),
+                (
+                    WebsiteDataTypes::LOCAL_STORAGE | WebsiteDataTypes::SESSION_STORAGE,
+                    self.local_storage_row.get(),
+                    self.local_storage_check.get(),
+                ),
+                (
+                    WebsiteDataTypes::INDEXEDDB_DATABASES,
+                    self.indexeddb_row.get(),
+                    self.indexeddb_check.get(),
+                ),
+                (
+                    WebsiteDataTypes::SERVICE_WORKER_REGISTRATIONS,
+                    self.service_workers_row.get(),
+                    self.service_workers_check.get(),
+                ),
+            ]
+        }
+        fn selected_types(&self) -> WebsiteDataTypes {
+            self.categories()
+                .into_iter()
+                .filter(|(_, _, check)| check.is_active())
+                .fold(WebsiteDataTypes::empty(), |types, (x, _, _)| types | x)
+        }
+
+        fn update_clear_button(&self) {
+            self.clear_button.set_sensitive(
+                self.data_manager.borrow().is_some() && !self.selected_types().is_empty(),
+            );
+        }
+
+        fn set_running(&self) {
+            self.data_manager.replace(None);
+            self.running_banner.set_revealed(true);
+            self.update_clear_button();
+        }
+
+        /// A session of its own on the data of an open app would fight with the app's
+        /// session over it, so the data is only touched while the app is closed
+        pub fn set_details(&self, details: &AppDetails) {
+            self.app_id.replace(details.id.clone());
+            let details = details.clone();
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                async move {
+                    if is_app_running(&details.id).await {
+                        _self.set_running();
+                        return;
+                    }
+                    _self
+                        .data_manager
+                        .replace(profiles::build_network_session(&details).website_data_manager());
+                    _self.update_clear_button();
+                    _self.update_usage().await;
+                }
+            ));
+        }
+
+        /// Shows how many sites store each kind of data
+        /// WebKit only knows the size of some kinds, the others are left at the count
+        async fn update_usage(&self) {
+            let Some(data_manager) = self.data_manager.borrow().clone() else {
+                return;
+            };
+            let data = match data_manager.fetch_future(WebsiteDataTypes::ALL).await {
+                Ok(data) => data,
+                Err(err) => {
+                    eprintln!("Failed to fetch website data: {err}");
+                    return;
+                }
+            };
+            for (types, row, _) in self.categories() {
+                let sites: Vec<&webkit::WebsiteData> = data
+                    .iter()
+                    .filter(|x| x.types().intersects(types))
+                    .collect();
+                let size: u64 = sites.iter().map(|x| x.size(types)).sum();
+                let usage = match (sites.len(), size) {
+                    (0, _) => "None".to_string(),
+                    (1, 0) => "1 site".to_string(),
+                    (count, 0) => format!("{count} sites"),
+                    (1, size) => format!("1 site, {}", glib::format_size(size)),
+                    (count, size) => format!("{count} sites, {}", glib::format_size(size)),
+                };
+                row.set_subtitle(usage.as_str());
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct ClearDataDialog(ObjectSubclass<imp::ClearDataDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl ClearDataDialog {
+    pub fn new(details: &AppDetails) -> Self {
+        let obj: Self = glib::Object::builder().build();
+        obj.imp().set_details(details);
+        obj
+    }
+}
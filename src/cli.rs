@@ -0,0 +1,199 @@
+use anyhow::{anyhow, bail};
+use ashpd::WindowIdentifier;
+use gtk::prelude::*;
+use url::Url;
+
+use crate::application::settings;
+use crate::apps::{
+    get_app_details, get_app_icon, get_app_size, install_app, is_app_installed, uninstall_app,
+    update_autostart, AppDetails,
+};
+use crate::create_app_dialog::gen_unique_id;
+use crate::profiles;
+use crate::util;
+
+fn app_details(id: &str) -> anyhow::Result<AppDetails> {
+    get_app_details(id).ok_or(anyhow!("No app with id {id:?}"))
+}
+
+/// Formats a setting the way `--set` takes it, text as it is and anything else as JSON
+fn format_setting(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        serde_json::Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Parses a value given to `--set` for a setting that currently holds `current`
+/// Text settings take the value as it is, unless it is a JSON object or list
+fn parse_setting(current: &serde_json::Value, value: &str) -> anyhow::Result<serde_json::Value> {
+    let json = serde_json::from_str::<serde_json::Value>(value);
+    Ok(match (current, json) {
+        (
+            serde_json::Value::String(_) | serde_json::Value::Null,
+            Ok(json @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))),
+        ) => json,
+        (serde_json::Value::String(_) | serde_json::Value::Null, _) => {
+            serde_json::Value::String(value.to_string())
+        }
+        (_, json) => json?,
+    })
+}
+
+/// Checks the settings that their types alone don't rule out
+fn validate_details(details: &AppDetails) -> anyhow::Result<()> {
+    Url::parse(&details.url).map_err(|err| anyhow!("Invalid url: {err}"))?;
+    if details.title.is_empty() {
+        bail!("Title can't be empty");
+    }
+    details.proxy.validate()?;
+    if details.zoom_level <= 0.0 {
+        bail!("Zoom level must be above 0");
+    }
+    if details
+        .download_dir
+        .as_deref()
+        .is_some_and(|x| !std::path::Path::new(x).is_absolute())
+    {
+        bail!("Download folder must be an absolute path");
+    }
+    if details
+        .theme_color
+        .as_deref()
+        .is_some_and(|x| gtk::gdk::RGBA::parse(x).is_err())
+    {
+        bail!("Theme color is not a CSS color");
+    }
+    if let Some(profile) = details
+        .profiles
+        .iter()
+        .find(|x| !profiles::is_valid_profile_id(&x.id))
+    {
+        bail!("{:?} is not a profile id", profile.id);
+    }
+    if !details
+        .all_profiles()
+        .iter()
+        .any(|x| x.id == details.profile)
+    {
+        bail!("No profile with id {:?}", details.profile);
+    }
+    Ok(())
+}
+
+pub fn list_applications() {
+    for id in settings().get::<Vec<String>>("app-ids") {
+        if let Some(details) = get_app_details(&id) {
+            println!("{id}\t{}", details.title);
+        }
+    }
+}
+
+/// Prints every app as a JSON array, including the ones with broken settings
+/// Sizes are in bytes, `installed` is null when the launcher portal can't be asked
+pub async fn list_applications_json() -> anyhow::Result<()> {
+    let mut apps = Vec::new();
+    for id in settings().get::<Vec<String>>("app-ids") {
+        let mut app = match get_app_details(&id) {
+            Some(details) => serde_json::to_value(details)?,
+            None => serde_json::json!({ "id": id }),
+        };
+        let size = get_app_size(&id).await;
+        app["data_size"] = size.data.into();
+        app["cache_size"] = size.cache.into();
+        // Unknown when the launcher portal can't be reached, like over SSH
+        app["installed"] = match is_app_installed(&id).await {
+            Ok(installed) => installed.into(),
+            Err(err) => {
+                eprintln!("Failed to check whether app {id} is installed: {err}");
+                serde_json::Value::Null
+            }
+        };
+        apps.push(app);
+    }
+    println!("{}", serde_json::to_string_pretty(&apps)?);
+    Ok(())
+}
+
+/// Prints every setting of an app as `key=value`, the same format `--set` takes
+pub fn show_app(id: &str) -> anyhow::Result<()> {
+    let settings = serde_json::to_value(app_details(id)?)?;
+    for (key, value) in settings.as_object().into_iter().flatten() {
+        println!("{key}={}", format_setting(value));
+    }
+    Ok(())
+}
+
+/// Creates an app, scraping the website for the title and icon
+/// Returns the id of the new app
+pub async fn create_app(url: &str, title: Option<String>) -> anyhow::Result<String> {
+    let url = util::parse_url(url)?;
+    let meta = util::get_website_meta(url.clone()).await?;
+    let title = title.or(meta.title).ok_or(anyhow!(
+        "Couldn't find a title for {url}, set one with --title"
+    ))?;
+    // Sites without a usable icon get one drawn from their title
+    let icon = match meta.icon {
+        Some(icon) => icon.buffer,
+        None => util::letter_icon(&title, &url, meta.theme_color.as_deref())?,
+    };
+
+    let details = AppDetails::new(gen_unique_id(), title, url.to_string());
+    install_app(&details, icon, &WindowIdentifier::default()).await?;
+    Ok(details.id)
+}
+
+pub async fn delete_app(id: &str) -> anyhow::Result<()> {
+    app_details(id)?;
+    uninstall_app(id).await
+}
+
+/// Applies a list of `key=value` assignments to an app's settings
+/// An empty value puts the setting back to its default
+pub async fn edit_app(id: &str, assignments: &[String]) -> anyhow::Result<()> {
+    let current = app_details(id)?;
+    let mut settings = serde_json::to_value(&current)?;
+    let fields = settings
+        .as_object_mut()
+        .ok_or(anyhow!("Settings of app {id} are not an object"))?;
+    // Also knows the settings that were put back to their default by an earlier assignment
+    let defaults = serde_json::to_value(AppDetails::default())?;
+    for assignment in assignments {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or(anyhow!("Expected KEY=VALUE, got {assignment:?}"))?;
+        let Some(default) = defaults.get(key).filter(|_| key != "id") else {
+            bail!("Unknown setting {key:?}");
+        };
+        let current_value = fields.get(key).unwrap_or(default);
+        if value.is_empty() {
+            fields.remove(key);
+        } else {
+            let value = parse_setting(current_value, value)
+                .map_err(|err| anyhow!("Invalid value for {key}: {err}"))?;
+            fields.insert(key.to_string(), value);
+        }
+        // Checked one by one so the error can name the setting
+        serde_json::from_value::<AppDetails>(serde_json::Value::Object(fields.clone()))
+            .map_err(|err| anyhow!("Invalid value for {key}: {err}"))?;
+    }
+
+    let details = serde_json::from_value::<AppDetails>(settings)?;
+    validate_details(&details)?;
+    if details.title != current.title {
+        // The launcher has to be reinstalled for a new name
+        install_app(
+            &details,
+            get_app_icon(id).await?,
+            &WindowIdentifier::default(),
+        )
+        .await?;
+    } else {
+        details.save()?;
+    }
+    if details.autostart != current.autostart {
+        update_autostart(None).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use gtk::glib;
+
+use crate::apps::{app_cache_dir, app_data_dir};
+
+/// Directory under the app's data directory holding the user's filter lists
+pub const FILTERS_DIR: &str = "filters";
+/// Where WebKit keeps the compiled filters, under the app's cache directory
+pub const COMPILED_FILTERS_DIR: &str = "content-filters";
+const BUNDLED_FILTERS: &str = include_str!("./tracker_filters.json");
+
+struct FilterList {
+    name: String,
+    source: String,
+}
+
+impl FilterList {
+    /// The source is part of the identifier so an edited list gets compiled again
+    /// A fixed hash keeps it the same across builds, compiled lists are stored on disk
+    fn identifier(&self) -> String {
+        let checksum =
+            glib::compute_checksum_for_data(glib::ChecksumType::Sha256, self.source.as_bytes())
+                .unwrap_or_default();
+        format!("{}-{checksum}", self.name)
+    }
+}
+
+pub fn filters_dir(id: &str) -> PathBuf {
+    app_data_dir(id).join(FILTERS_DIR)
+}
+
+/// Filter lists the user added to the app
+pub fn user_filter_lists(id: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(filters_dir(id)) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|x| x == "json"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Copies a WebKit content blocker list into the app, checking that it is one first
+/// https://webkit.org/blog/3476/content-blockers-first-look/
+pub fn add_filter_list(id: &str, path: &Path) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(path)?;
+    let Ok(serde_json::Value::Array(rules)) = serde_json::from_str(&source) else {
+        bail!("Not a content blocker list");
+    };
+    if rules
+        .iter()
+        .any(|x| x.get("trigger").is_none() || x.get("action").is_none())
+    {
+        bail!("Not a content blocker list");
+    }
+
+    let Some(name) = path.file_stem() else {
+        bail!("Invalid file name");
+    };
+    let dir = filters_dir(id);
+    let target = dir.join(format!("{}.json", name.to_string_lossy()));
+    if target.exists() {
+        bail!(
+            "A filter list named {} was already added",
+            name.to_string_lossy()
+        );
+    }
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(target, source)?;
+    Ok(())
+}
+
+pub fn remove_filter_list(path: &Path) -> anyhow::Result<()> {
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+fn filter_lists(id: &str) -> Vec<FilterList> {
+    let mut lists = vec![FilterList {
+        name: "trackers".to_string(),
+        source: BUNDLED_FILTERS.to_string(),
+    }];
+    for path in user_filter_lists(id) {
+        match std::fs::read_to_string(&path) {
+            Ok(source) => lists.push(FilterList {
+                name: format!(
+                    "user-{}",
+                    path.file_stem().unwrap_or_default().to_string_lossy()
+                ),
+                source,
+            }),
+            Err(err) => eprintln!("Failed to read filter list {path:?}: {err}"),
+        }
+    }
+    lists
+}
+
+/// Compiles the app's filter lists, reusing the ones that were already compiled
+/// Lists that fail to compile are skipped so one bad list doesn't disable the rest
+pub async fn load_filters(id: &str) -> Vec<webkit::UserContentFilter> {
+    let store_dir = app_cache_dir(id).join(COMPILED_FILTERS_DIR);
+    let store = webkit::UserContentFilterStore::new(store_dir.to_str().unwrap());
+
+    let lists = filter_lists(id);
+    let identifiers: Vec<String> = lists.iter().map(|x| x.identifier()).collect();
+    let mut filters = Vec::new();
+    for (list, identifier) in lists.iter().zip(&identifiers) {
+        let filter = match store.load_future(identifier).await {
+            Ok(filter) => Ok(filter),
+            Err(_) => {
+                store
+                    .save_future(identifier, &glib::Bytes::from(list.source.as_bytes()))
+                    .await
+            }
+        };
+        match filter {
+            Ok(filter) => filters.push(filter),
+            Err(err) => eprintln!("Failed to compile filter list {}: {err}", list.name),
+        }
+    }
+
+    // Drop lists that were removed or changed since they were compiled
+    if let Ok(stored) = store.fetch_identifiers_future().await {
+        for identifier in stored {
+            if !identifiers.iter().any(|x| x == identifier.as_str()) {
+                if let Err(err) = store.remove_future(&identifier).await {
+                    eprintln!("Failed to remove compiled filter list: {err}");
+                }
+            }
+        }
+    }
+
+    filters
+}
@@ -1,5 +1,6 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
+use glib::clone;
 use gtk::{gio, glib};
 use rand::Rng;
 use std::cell::{Cell, RefCell};
@@ -10,12 +11,12 @@ use ashpd::WindowIdentifier;
 
 use crate::{
     application::settings,
-    apps::{install_app, AppDetails},
-    util,
+    apps::{app_cache_dir, app_data_dir, copy_app_dir, install_app, update_autostart, AppDetails},
+    icon_editor_dialog::IconEditorDialog,
+    scope,
+    util::{self, IconStyle},
 };
 
-use anyhow::bail;
-
 pub const APP_ID_LENGTH: usize = 10;
 
 fn gen_id() -> String {
@@ -44,6 +45,16 @@ mod imp {
     #[properties(wrapper_type = super::CreateAppDialog)]
     pub struct CreateAppDialog {
         unsaved_icon: RefCell<Option<Vec<u8>>>,
+        // Taken from the website's manifest, if it has one
+        unsaved_theme_color: RefCell<Option<String>>,
+        unsaved_scope: RefCell<Option<Url>>,
+        // The app being duplicated, if any
+        source: RefCell<Option<AppDetails>>,
+        // Whether the icon is a letter icon that follows the title
+        generated_icon: Cell<bool>,
+        // The image the icon was edited from and how, edits start over from it
+        icon_source: RefCell<Option<Vec<u8>>>,
+        icon_style: RefCell<Option<IconStyle>>,
 
         #[template_child]
         pub url_entry: TemplateChild<adw::EntryRow>,
@@ -60,6 +71,8 @@ mod imp {
         #[template_child]
         pub icon_image: TemplateChild<gtk::Image>,
         #[template_child]
+        pub edit_icon_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub title_entry: TemplateChild<adw::EntryRow>,
 
         #[property(get, set)]
@@ -91,7 +104,7 @@ mod imp {
         #[template_callback]
         async fn on_url_apply(&self, entry: adw::EntryRow) {
             self.obj().set_loading(true);
-            if let Ok(url) = self.parse_url(entry.text().as_str()) {
+            if let Ok(url) = util::parse_url(entry.text().as_str()) {
                 // Disable apply button when setting text
                 // so it won't keep coming back on every apply
                 self.url_entry.set_show_apply_button(false);
@@ -100,14 +113,27 @@ mod imp {
 
                 match util::get_website_meta(url).await {
                     Ok(meta) => {
+                        if let Some(start_url) = &meta.start_url {
+                            self.url_entry.set_show_apply_button(false);
+                            self.url_entry.set_text(start_url.as_str());
+                            self.url_entry.set_show_apply_button(true);
+                        }
+                        self.unsaved_theme_color
+                            .replace(meta.theme_color.or(meta.background_color));
+                        self.unsaved_scope.replace(meta.scope);
                         self.title_entry
                             .set_text(meta.title.unwrap_or_default().as_str());
-                        self.unsaved_icon
-                            .replace(meta.icon.as_ref().map(|x| x.buffer.clone()));
-                        self.icon_image
-                            .set_paintable(meta.icon.map(|x| x.to_gdk_texture(32)).as_ref());
+                        match meta.icon {
+                            Some(icon) => self.set_unsaved_icon(icon.buffer),
+                            None => self.set_generated_icon(),
+                        }
+                    }
+                    Err(err) => {
+                        self.toast(err.to_string());
+                        if self.unsaved_icon.borrow().is_none() || self.generated_icon.get() {
+                            self.set_generated_icon();
+                        }
                     }
-                    Err(err) => self.toast(err.to_string()),
                 }
                 self.validate_input();
             } else {
@@ -121,35 +147,46 @@ mod imp {
             self.validate_input();
         }
         #[template_callback]
+        fn on_title_changed(&self, _: gtk::Widget) {
+            if self.generated_icon.get() {
+                self.set_generated_icon();
+            }
+            self.validate_input();
+        }
+        #[template_callback]
         async fn on_icon_clicked(&self, _: gtk::Button) {
             if let Ok(file) =
                 util::icon_from_dialog(self.obj().root().and_downcast_ref::<gtk::Window>()).await
             {
-                if let Err(err) = self.set_unsaved_icon(&file).await {
+                if let Err(err) = self.edit_icon_file(&file).await {
                     self.toast(err.to_string())
-                } else {
-                    self.validate_input();
                 }
             }
         }
         #[template_callback]
+        fn on_edit_icon_clicked(&self, _: gtk::Button) {
+            let source = self.icon_source.borrow().clone();
+            let Some(source) = source.or(self.unsaved_icon.borrow().clone()) else {
+                return;
+            };
+            let style = self.icon_style.borrow().clone();
+            if let Err(err) = self.edit_icon(source, style.as_ref()) {
+                self.toast(err.to_string())
+            }
+        }
+        #[template_callback]
         async fn on_create_clicked(&self, _: gtk::Button) {
             if self.validate_input() {
                 self.button.set_sensitive(false);
                 self.button_stack
                     .set_visible_child(&self.button_spinner.get());
 
-                if let Err(err) = install_app(
-                    &AppDetails::new(
-                        gen_unique_id(),
-                        self.title_entry.text().to_string(),
-                        self.url_entry.text().to_string(),
-                    ),
-                    self.unsaved_icon.take().unwrap(),
-                    &WindowIdentifier::from_native(&self.obj().root().unwrap()).await,
-                )
-                .await
-                {
+                let source = self.source.borrow().clone();
+                let result = match source {
+                    Some(source) => self.duplicate_app(&source).await,
+                    None => self.create_app().await,
+                };
+                if let Err(err) = result {
                     self.toast(err.to_string());
                 } else {
                     self.obj().activate_action("win.refresh", None).unwrap();
@@ -163,23 +200,81 @@ mod imp {
     }
 
     impl CreateAppDialog {
-        fn parse_url(&self, url: &str) -> anyhow::Result<Url> {
-            if let Ok(url) = Url::parse(url) {
-                Ok(url)
-            } else {
-                let url = format!("https://{url}");
-                if let Ok(url) = Url::parse(url.as_str()) {
-                    Ok(url)
-                } else {
-                    bail!("");
+        async fn create_app(&self) -> anyhow::Result<()> {
+            let mut details = AppDetails::new(
+                gen_unique_id(),
+                self.title_entry.text().to_string(),
+                self.url_entry.text().to_string(),
+            );
+            details.theme_color = self.unsaved_theme_color.borrow().clone();
+            if let Some(scope) = self.unsaved_scope.borrow().as_ref() {
+                for host in scope::default_scope(scope.as_str()) {
+                    if !details.scope.contains(&host) {
+                        details.scope.push(host);
+                    }
                 }
             }
+
+            install_app(
+                &details,
+                self.unsaved_icon.take().unwrap(),
+                &WindowIdentifier::from_native(&self.obj().root().unwrap()).await,
+            )
+            .await
+        }
+
+        /// Installs a second launcher with the source app's settings and a copy of
+        /// its data, so the duplicate starts out logged in
+        async fn duplicate_app(&self, source: &AppDetails) -> anyhow::Result<()> {
+            let details = AppDetails {
+                id: gen_unique_id(),
+                title: self.title_entry.text().to_string(),
+                ..source.clone()
+            };
+            copy_app_dir(&source.id, &details.id)?;
+
+            let wid = WindowIdentifier::from_native(&self.obj().root().unwrap()).await;
+            let icon = self.unsaved_icon.borrow().clone().unwrap();
+            if let Err(err) = install_app(&details, icon, &wid).await {
+                // Don't leave the copied data behind when the launcher wasn't installed
+                for dir in [app_data_dir(&details.id), app_cache_dir(&details.id)] {
+                    if dir.exists() {
+                        std::fs::remove_dir_all(dir)?;
+                    }
+                }
+                return Err(err);
+            }
+            if details.autostart {
+                update_autostart(Some(wid)).await?;
+            }
+            Ok(())
         }
+
+        pub fn set_source(&self, source: &AppDetails, icon: Vec<u8>) {
+            self.obj().set_title("Duplicate Application");
+            self.button_label.set_label("Duplicate");
+
+            // The copied data belongs to the same website
+            self.url_entry.set_text(&source.url);
+            self.url_entry.set_editable(false);
+            self.url_entry.set_show_apply_button(false);
+
+            self.title_entry
+                .set_text(&format!("{} (Copy)", source.title));
+            self.icon_image
+                .set_paintable(Some(&util::to_gdk_texture(&icon, 32)));
+            self.unsaved_icon.replace(Some(icon));
+            self.source.replace(Some(source.clone()));
+            self.validate_input();
+        }
+
         fn validate_input(&self) -> bool {
             let valid = Url::parse(self.url_entry.text().as_str()).is_ok()
                 && self.unsaved_icon.borrow().is_some()
                 && !self.title_entry.text().is_empty();
             self.url_entry.set_css_classes(&[]);
+            self.edit_icon_button
+                .set_sensitive(self.unsaved_icon.borrow().is_some());
             self.button.set_sensitive(valid);
             valid
         }
@@ -189,20 +284,57 @@ mod imp {
                 .add_toast(adw::Toast::new(message.as_str()));
         }
 
-        async fn set_unsaved_icon(&self, file: &gio::File) -> anyhow::Result<()> {
+        async fn edit_icon_file(&self, file: &gio::File) -> anyhow::Result<()> {
             let (buffer, _etag) = file.load_contents_future().await?;
-            let extension = file.basename();
-            let extension = extension
+            self.edit_icon(buffer.to_vec(), None)
+        }
+
+        /// Opens the icon editor, the edited icon replaces the current one once saved
+        fn edit_icon(&self, source: Vec<u8>, style: Option<&IconStyle>) -> anyhow::Result<()> {
+            let theme_color = self.unsaved_theme_color.borrow().clone().or(self
+                .source
+                .borrow()
                 .as_ref()
-                .and_then(|x| x.extension())
-                .and_then(|x| x.to_str());
-            let image =
-                util::Image::from_buffer(buffer.to_vec(), extension.is_some_and(|x| x == "svg"))?;
-            self.unsaved_icon.replace(Some(image.buffer.to_vec()));
-            self.icon_image
-                .set_paintable(Some(&image.to_gdk_texture(32)));
+                .and_then(|x| x.theme_color.clone()));
+            let dialog = IconEditorDialog::new(&source, theme_color.as_deref(), style)?;
+            dialog.connect_saved(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |dialog, icon| {
+                    _self.set_unsaved_icon(icon);
+                    _self.icon_source.replace(Some(source.clone()));
+                    _self.icon_style.replace(Some(dialog.style()));
+                }
+            ));
+            dialog.present(Some(&*self.obj()));
             Ok(())
         }
+
+        /// Sets an icon as it is, edits of an earlier one are forgotten
+        fn set_unsaved_icon(&self, icon: Vec<u8>) {
+            self.icon_image
+                .set_paintable(Some(&util::to_gdk_texture(&icon, 32)));
+            self.unsaved_icon.replace(Some(icon));
+            self.icon_source.replace(None);
+            self.icon_style.replace(None);
+            self.generated_icon.set(false);
+            self.validate_input();
+        }
+
+        /// Falls back to a letter icon so the app can be created without an image file
+        fn set_generated_icon(&self) {
+            let Ok(url) = Url::parse(self.url_entry.text().as_str()) else {
+                return;
+            };
+            let theme_color = self.unsaved_theme_color.borrow().clone();
+            match util::letter_icon(&self.title_entry.text(), &url, theme_color.as_deref()) {
+                Ok(icon) => {
+                    self.set_unsaved_icon(icon);
+                    self.generated_icon.set(true);
+                }
+                Err(err) => self.toast(format!("Failed to generate an icon: {err}")),
+            }
+        }
     }
 }
 
@@ -216,6 +348,13 @@ impl CreateAppDialog {
     pub fn new() -> Self {
         glib::Object::builder().build()
     }
+
+    /// Opens the dialog to install a copy of an existing app under a new id
+    pub fn duplicate(source: &AppDetails, icon: Vec<u8>) -> Self {
+        let obj = Self::new();
+        obj.imp().set_source(source, icon);
+        obj
+    }
 }
 
 impl Default for CreateAppDialog {
@@ -0,0 +1,210 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::clone;
+use glib::Object;
+use gtk::{gio, glib};
+use std::cell::RefCell;
+use webkit::prelude::*;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum DownloadState {
+    #[default]
+    Active,
+    Finished,
+    Cancelled,
+    Failed(String),
+}
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/zaedus/spider/download_row.ui")]
+    pub struct DownloadRow {
+        #[template_child]
+        pub title: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub subtitle: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub progress_bar: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub cancel_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub open_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub show_button: TemplateChild<gtk::Button>,
+
+        pub download: RefCell<Option<webkit::Download>>,
+        pub state: RefCell<DownloadState>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DownloadRow {
+        const NAME: &'static str = "DownloadRow";
+        type Type = super::DownloadRow;
+        type ParentType = gtk::ListBoxRow;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DownloadRow {}
+    impl WidgetImpl for DownloadRow {}
+    impl ListBoxRowImpl for DownloadRow {}
+
+    #[gtk::template_callbacks]
+    impl DownloadRow {
+        #[template_callback]
+        fn on_cancel_clicked(&self, _: gtk::Button) {
+            if let Some(download) = self.download.borrow().as_ref() {
+                download.cancel();
+            }
+        }
+        #[template_callback]
+        async fn on_open_clicked(&self, _: gtk::Button) {
+            if let Some(file) = self.file() {
+                let launcher = gtk::FileLauncher::new(Some(&file));
+                if let Err(err) = launcher.launch_future(self.window().as_ref()).await {
+                    eprintln!("Failed to open download: {err}");
+                }
+            }
+        }
+        #[template_callback]
+        async fn on_show_clicked(&self, _: gtk::Button) {
+            if let Some(file) = self.file() {
+                let launcher = gtk::FileLauncher::new(Some(&file));
+                if let Err(err) = launcher
+                    .open_containing_folder_future(self.window().as_ref())
+                    .await
+                {
+                    eprintln!("Failed to show download: {err}");
+                }
+            }
+        }
+    }
+
+    impl DownloadRow {
+        fn file(&self) -> Option<gio::File> {
+            let destination = self.download.borrow().as_ref()?.destination()?;
+            Some(gio::File::for_path(destination.as_str()))
+        }
+        fn window(&self) -> Option<gtk::Window> {
+            self.obj().root().and_downcast::<gtk::Window>()
+        }
+
+        pub fn set_download(&self, download: &webkit::Download) {
+            self.download.replace(Some(download.clone()));
+            self.update();
+
+            download.connect_created_destination(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, _| _self.update()
+            ));
+            download.connect_received_data(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, _| _self.update()
+            ));
+            download.connect_failed(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, err| {
+                    _self
+                        .state
+                        .replace(if err.matches(webkit::DownloadError::CancelledByUser) {
+                            DownloadState::Cancelled
+                        } else {
+                            DownloadState::Failed(err.message().to_string())
+                        });
+                    _self.update();
+                }
+            ));
+            // Also emitted after a failure, which has already set the state
+            download.connect_finished(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_| {
+                    if *_self.state.borrow() == DownloadState::Active {
+                        _self.state.replace(DownloadState::Finished);
+                    }
+                    _self.update();
+                }
+            ));
+        }
+
+        fn update(&self) {
+            let download = self.download.borrow();
+            let Some(download) = download.as_ref() else {
+                return;
+            };
+            let destination = download.destination();
+            let name = destination
+                .as_ref()
+                .and_then(|x| {
+                    std::path::Path::new(x.as_str())
+                        .file_name()
+                        .map(|x| x.to_string_lossy().to_string())
+                })
+                .or_else(|| {
+                    download
+                        .response()
+                        .and_then(|x| x.suggested_filename())
+                        .map(|x| x.to_string())
+                })
+                .unwrap_or_else(|| "Download".to_string());
+            self.title.set_label(name.as_str());
+            self.title
+                .set_tooltip_text(destination.as_ref().map(|x| x.as_str()));
+
+            let received = download.received_data_length();
+            let total = download.response().map(|x| x.content_length()).unwrap_or(0);
+            let state = self.state.borrow();
+            let status = match &*state {
+                DownloadState::Active if total > 0 => format!(
+                    "{} of {}",
+                    glib::format_size(received),
+                    glib::format_size(total)
+                ),
+                DownloadState::Active => glib::format_size(received).to_string(),
+                DownloadState::Finished => glib::format_size(received).to_string(),
+                DownloadState::Cancelled => "Cancelled".to_string(),
+                DownloadState::Failed(message) => format!("Failed: {message}"),
+            };
+            self.subtitle.set_label(status.as_str());
+
+            let active = *state == DownloadState::Active;
+            let finished = *state == DownloadState::Finished;
+            self.progress_bar.set_visible(active);
+            self.progress_bar
+                .set_fraction(download.estimated_progress());
+            self.cancel_button.set_visible(active);
+            self.open_button.set_visible(finished);
+            self.show_button.set_visible(finished);
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DownloadRow(ObjectSubclass<imp::DownloadRow>)
+        @extends gtk::ListBoxRow, gtk::Widget,
+        @implements gtk::Accessible, gtk::Actionable, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DownloadRow {
+    pub fn new(download: &webkit::Download) -> Self {
+        let obj: Self = Object::builder().build();
+        obj.imp().set_download(download);
+        obj
+    }
+
+    pub fn state(&self) -> DownloadState {
+        self.imp().state.borrow().clone()
+    }
+}
@@ -0,0 +1,241 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::subclass::Signal;
+use gtk::{gdk, glib};
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use crate::util::{self, IconFit, IconStyle, EDITED_ICON_SIZE};
+
+/// Images up to this size are favicons that get blurry when scaled smoothly
+const SMALL_ICON_SIZE: u32 = 32;
+
+// Indices of the choices in the combo rows
+fn index_to_fit(index: u32) -> IconFit {
+    match index {
+        1 => IconFit::Crop,
+        _ => IconFit::Pad,
+    }
+}
+
+fn fit_to_index(fit: IconFit) -> u32 {
+    match fit {
+        IconFit::Pad => 0,
+        IconFit::Crop => 1,
+    }
+}
+
+fn pixel_to_rgba(pixel: &image::Rgba<u8>) -> gdk::RGBA {
+    let channel = |x: u8| x as f32 / 255.0;
+    gdk::RGBA::new(
+        channel(pixel[0]),
+        channel(pixel[1]),
+        channel(pixel[2]),
+        channel(pixel[3]),
+    )
+}
+
+fn rgba_to_pixel(color: &gdk::RGBA) -> image::Rgba<u8> {
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    image::Rgba([
+        channel(color.red()),
+        channel(color.green()),
+        channel(color.blue()),
+        channel(color.alpha()),
+    ])
+}
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/zaedus/spider/icon_editor_dialog.ui")]
+    pub struct IconEditorDialog {
+        pub source: RefCell<Option<image::DynamicImage>>,
+        pub theme_color: RefCell<Option<image::Rgba<u8>>>,
+        // The icon as shown in the preview, handed out on save
+        pub icon: RefCell<Option<Vec<u8>>>,
+
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub save_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub preview_image: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub fit_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub background_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub background_model: TemplateChild<gtk::StringList>,
+        #[template_child]
+        pub color_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub color_button: TemplateChild<gtk::ColorDialogButton>,
+        #[template_child]
+        pub rounded_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub sharp_row: TemplateChild<adw::SwitchRow>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for IconEditorDialog {
+        const NAME: &'static str = "IconEditorDialog";
+        type Type = super::IconEditorDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for IconEditorDialog {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![Signal::builder("saved")
+                    .param_types([glib::Bytes::static_type()])
+                    .build()]
+            })
+        }
+    }
+    impl WidgetImpl for IconEditorDialog {}
+    impl AdwDialogImpl for IconEditorDialog {}
+
+    #[gtk::template_callbacks]
+    impl IconEditorDialog {
+        #[template_callback]
+        fn on_cancel_clicked(&self, _: gtk::Button) {
+            self.obj().close();
+        }
+        #[template_callback]
+        fn on_style_changed(&self, _: glib::ParamSpec) {
+            self.color_row.set_visible(self.has_custom_background());
+            self.update_preview();
+        }
+        #[template_callback]
+        fn on_save_clicked(&self, _: gtk::Button) {
+            let Some(icon) = self.icon.borrow().clone() else {
+                return;
+            };
+            self.obj()
+                .emit_by_name::<()>("saved", &[&glib::Bytes::from_owned(icon)]);
+            self.obj().close();
+        }
+    }
+
+    impl IconEditorDialog {
+        pub fn set_source(&self, source: image::DynamicImage, theme_color: Option<gdk::RGBA>) {
+            let side = source.width().max(source.height());
+            self.source.replace(Some(source));
+            if theme_color.is_none() {
+                self.background_model.remove(1);
+            }
+            self.color_button
+                .set_rgba(theme_color.as_ref().unwrap_or(&gdk::RGBA::WHITE));
+            self.theme_color
+                .replace(theme_color.as_ref().map(rgba_to_pixel));
+            self.sharp_row.set_active(side <= SMALL_ICON_SIZE);
+            self.update_preview();
+        }
+
+        /// Brings back the choices of an earlier edit of the same image
+        pub fn set_style(&self, style: &IconStyle) {
+            self.fit_row.set_selected(fit_to_index(style.fit));
+            let theme_color = *self.theme_color.borrow();
+            match style.background {
+                None => self.background_row.set_selected(0),
+                Some(color) if Some(color) == theme_color => self.background_row.set_selected(1),
+                Some(color) => {
+                    self.color_button.set_rgba(&pixel_to_rgba(&color));
+                    self.background_row
+                        .set_selected(self.background_model.n_items() - 1);
+                }
+            }
+            self.rounded_row.set_active(style.rounded);
+            self.sharp_row.set_active(style.sharp);
+        }
+
+        fn has_custom_background(&self) -> bool {
+            // The last choice, whether or not the theme color is there
+            self.background_row.selected() + 1 == self.background_model.n_items()
+        }
+
+        pub fn style(&self) -> IconStyle {
+            let background = match self.background_row.selected() {
+                0 => None,
+                _ if self.has_custom_background() => Some(rgba_to_pixel(&self.color_button.rgba())),
+                _ => *self.theme_color.borrow(),
+            };
+            IconStyle {
+                fit: index_to_fit(self.fit_row.selected()),
+                background,
+                rounded: self.rounded_row.is_active(),
+                sharp: self.sharp_row.is_active(),
+            }
+        }
+
+        fn update_preview(&self) {
+            let Some(source) = self.source.borrow().clone() else {
+                return;
+            };
+            match util::render_icon(&source, &self.style()) {
+                Ok(icon) => {
+                    self.preview_image
+                        .set_paintable(Some(&util::to_gdk_texture(&icon, EDITED_ICON_SIZE as i32)));
+                    self.icon.replace(Some(icon));
+                    self.save_button.set_sensitive(true);
+                }
+                Err(err) => {
+                    self.save_button.set_sensitive(false);
+                    self.toast_overlay
+                        .add_toast(adw::Toast::new(err.to_string().as_str()));
+                }
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct IconEditorDialog(ObjectSubclass<imp::IconEditorDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl IconEditorDialog {
+    /// Edits an image file or fetched icon, SVGs are told apart by their content
+    /// The theme color is offered as a background when the app has one, and the style
+    /// of an earlier edit is restored when given so it can be changed or undone
+    pub fn new(
+        buffer: &[u8],
+        theme_color: Option<&str>,
+        style: Option<&IconStyle>,
+    ) -> anyhow::Result<Self> {
+        let source = util::decode_icon(buffer, image::guess_format(buffer).is_err())?;
+        let obj: Self = glib::Object::builder().build();
+        obj.imp()
+            .set_source(source, theme_color.and_then(|x| gdk::RGBA::parse(x).ok()));
+        if let Some(style) = style {
+            obj.imp().set_style(style);
+        }
+        Ok(obj)
+    }
+
+    pub fn style(&self) -> IconStyle {
+        self.imp().style()
+    }
+
+    /// Called with the edited icon as a PNG
+    pub fn connect_saved<F: Fn(&Self, Vec<u8>) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "saved",
+            false,
+            glib::closure_local!(move |obj: &Self, icon: glib::Bytes| f(obj, icon.to_vec())),
+        )
+    }
+}
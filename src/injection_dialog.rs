@@ -0,0 +1,181 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::subclass::Signal;
+use gtk::glib;
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use crate::injections::{self, Injection, InjectionKind, InjectionTime};
+
+// Indices of the choices in the combo rows
+fn kind_to_index(kind: InjectionKind) -> u32 {
+    match kind {
+        InjectionKind::Style => 0,
+        InjectionKind::Script => 1,
+    }
+}
+
+fn index_to_kind(index: u32) -> InjectionKind {
+    match index {
+        1 => InjectionKind::Script,
+        _ => InjectionKind::Style,
+    }
+}
+
+fn time_to_index(time: InjectionTime) -> u32 {
+    match time {
+        InjectionTime::End => 0,
+        InjectionTime::Start => 1,
+    }
+}
+
+fn index_to_time(index: u32) -> InjectionTime {
+    match index {
+        1 => InjectionTime::Start,
+        _ => InjectionTime::End,
+    }
+}
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/zaedus/spider/injection_dialog.ui")]
+    pub struct InjectionDialog {
+        pub app_id: RefCell<String>,
+        // Kept to retain the id when editing an existing injection
+        pub injection: RefCell<Option<Injection>>,
+
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub save_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub name_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub kind_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub all_frames_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub time_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub source_view: TemplateChild<gtk::TextView>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for InjectionDialog {
+        const NAME: &'static str = "InjectionDialog";
+        type Type = super::InjectionDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for InjectionDialog {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("saved").build()])
+        }
+    }
+    impl WidgetImpl for InjectionDialog {}
+    impl AdwDialogImpl for InjectionDialog {}
+
+    #[gtk::template_callbacks]
+    impl InjectionDialog {
+        #[template_callback]
+        fn on_cancel_clicked(&self, _: gtk::Button) {
+            self.obj().close();
+        }
+        #[template_callback]
+        fn validate_input_cb(&self, _: gtk::Widget) {
+            self.save_button
+                .set_sensitive(!self.name_entry.text().trim().is_empty());
+        }
+        #[template_callback]
+        fn on_kind_changed(&self, _: glib::ParamSpec) {
+            // Style sheets have no injection time
+            self.time_row
+                .set_sensitive(index_to_kind(self.kind_row.selected()) == InjectionKind::Script);
+        }
+        #[template_callback]
+        fn on_save_clicked(&self, _: gtk::Button) {
+            let mut injection = self
+                .injection
+                .borrow()
+                .clone()
+                .unwrap_or_else(|| Injection::new(InjectionKind::Style));
+            injection.name = self.name_entry.text().to_string();
+            injection.kind = index_to_kind(self.kind_row.selected());
+            injection.all_frames = self.all_frames_row.is_active();
+            injection.time = index_to_time(self.time_row.selected());
+
+            let buffer = self.source_view.buffer();
+            let source = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+
+            match injections::save_injection(&self.app_id.borrow(), injection, source.as_str()) {
+                Ok(_) => {
+                    self.obj().emit_by_name::<()>("saved", &[]);
+                    self.obj().close();
+                }
+                Err(err) => self
+                    .toast_overlay
+                    .add_toast(adw::Toast::new(err.to_string().as_str())),
+            }
+        }
+    }
+
+    impl InjectionDialog {
+        pub fn set_injection(&self, app_id: &str, injection: Option<&Injection>) {
+            self.app_id.replace(app_id.to_string());
+            self.injection.replace(injection.cloned());
+
+            let injection = injection
+                .cloned()
+                .unwrap_or_else(|| Injection::new(InjectionKind::Style));
+            self.name_entry.set_text(injection.name.as_str());
+            self.kind_row.set_selected(kind_to_index(injection.kind));
+            self.all_frames_row.set_active(injection.all_frames);
+            self.time_row.set_selected(time_to_index(injection.time));
+            self.time_row
+                .set_sensitive(injection.kind == InjectionKind::Script);
+            if !injection.id.is_empty() {
+                match injections::read_source(app_id, &injection) {
+                    Ok(source) => self.source_view.buffer().set_text(source.as_str()),
+                    Err(err) => self
+                        .toast_overlay
+                        .add_toast(adw::Toast::new(err.to_string().as_str())),
+                }
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct InjectionDialog(ObjectSubclass<imp::InjectionDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl InjectionDialog {
+    /// Edits an existing injection of the app, or creates a new one when none is given
+    pub fn new(app_id: &str, injection: Option<&Injection>) -> Self {
+        let obj: Self = glib::Object::builder().build();
+        obj.imp().set_injection(app_id, injection);
+        obj
+    }
+
+    pub fn connect_saved<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "saved",
+            false,
+            glib::closure_local!(move |obj: &Self| f(obj)),
+        )
+    }
+}
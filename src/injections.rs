@@ -0,0 +1,218 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::apps::{app_data_dir, get_app_details, AppDetails};
+
+pub const INJECTIONS_DIR: &str = "injections";
+const INJECTION_ID_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionKind {
+    Style,
+    Script,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionTime {
+    Start,
+    #[default]
+    End,
+}
+
+/// A user supplied style sheet or script, its source is kept in a file under
+/// the app's data directory so it can be edited with any text editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Injection {
+    pub id: String,
+    pub name: String,
+    pub kind: InjectionKind,
+    /// Inject into iframes as well as the top frame
+    #[serde(default)]
+    pub all_frames: bool,
+    /// When scripts run, style sheets always apply right away
+    #[serde(default)]
+    pub time: InjectionTime,
+}
+
+impl Injection {
+    pub fn new(kind: InjectionKind) -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            kind,
+            all_frames: false,
+            time: InjectionTime::End,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match self.kind {
+            InjectionKind::Style => format!("{}.css", self.id),
+            InjectionKind::Script => format!("{}.js", self.id),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![match self.kind {
+            InjectionKind::Style => "Style sheet",
+            InjectionKind::Script => "Script",
+        }];
+        parts.push(if self.all_frames {
+            "all frames"
+        } else {
+            "main frame"
+        });
+        if self.kind == InjectionKind::Script {
+            parts.push(match self.time {
+                InjectionTime::Start => "runs at page start",
+                InjectionTime::End => "runs at page end",
+            });
+        }
+        parts.join(", ")
+    }
+}
+
+// Ids end up in file names
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|x| x.is_ascii_alphanumeric())
+}
+
+/// Injections are stored in the app's settings as a JSON list
+pub fn parse_injections(value: &str) -> anyhow::Result<Vec<Injection>> {
+    let injections: Vec<Injection> = serde_json::from_str(value)?;
+    if injections.iter().any(|x| !is_valid_id(&x.id)) {
+        bail!("Invalid injection id");
+    }
+    Ok(injections)
+}
+
+pub fn injections_to_string(injections: &[Injection]) -> String {
+    serde_json::to_string(injections).unwrap()
+}
+
+pub fn injections_dir(id: &str) -> PathBuf {
+    app_data_dir(id).join(INJECTIONS_DIR)
+}
+
+pub fn source_path(id: &str, injection: &Injection) -> PathBuf {
+    injections_dir(id).join(injection.file_name())
+}
+
+pub fn read_source(id: &str, injection: &Injection) -> anyhow::Result<String> {
+    Ok(std::fs::read_to_string(source_path(id, injection))?)
+}
+
+fn gen_injection_id(details: &AppDetails) -> String {
+    loop {
+        let id: String = rand::thread_rng()
+            .sample_iter(rand::distributions::Uniform::from('a'..='z'))
+            .take(INJECTION_ID_LENGTH)
+            .collect();
+        if !details.injections.iter().any(|x| x.id == id) {
+            return id;
+        }
+    }
+}
+
+/// Writes the source and adds or replaces the injection in the app's settings
+/// An injection without an id is given a new one
+pub fn save_injection(
+    id: &str,
+    mut injection: Injection,
+    source: &str,
+) -> anyhow::Result<AppDetails> {
+    let Some(mut details) = get_app_details(id) else {
+        bail!("No app with id {id}");
+    };
+    if injection.name.trim().is_empty() {
+        bail!("Name can't be empty");
+    }
+    injection.name = injection.name.trim().to_string();
+
+    match details.injections.iter().position(|x| x.id == injection.id) {
+        Some(idx) => {
+            // The extension changes with the kind, so don't leave the old file behind
+            let old_path = source_path(id, &details.injections[idx]);
+            if old_path != source_path(id, &injection) && old_path.exists() {
+                std::fs::remove_file(old_path)?;
+            }
+            details.injections[idx] = injection.clone();
+        }
+        None => {
+            injection.id = gen_injection_id(&details);
+            details.injections.push(injection.clone());
+        }
+    }
+
+    std::fs::create_dir_all(injections_dir(id))?;
+    std::fs::write(source_path(id, &injection), source)?;
+    details.save()?;
+    Ok(details)
+}
+
+pub fn delete_injection(id: &str, injection_id: &str) -> anyhow::Result<AppDetails> {
+    let Some(mut details) = get_app_details(id) else {
+        bail!("No app with id {id}");
+    };
+    if let Some(idx) = details.injections.iter().position(|x| x.id == injection_id) {
+        let injection = details.injections.remove(idx);
+        let path = source_path(id, &injection);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    details.save()?;
+    Ok(details)
+}
+
+/// Adds the app's injections to a content manager
+/// Ones with a missing source file are skipped
+pub fn add_to_content_manager(
+    id: &str,
+    injections: &[Injection],
+    content_manager: &webkit::UserContentManager,
+) {
+    for injection in injections {
+        let source = match read_source(id, injection) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Failed to read {}: {err}", injection.name);
+                continue;
+            }
+        };
+        let frames = if injection.all_frames {
+            webkit::UserContentInjectedFrames::AllFrames
+        } else {
+            webkit::UserContentInjectedFrames::TopFrame
+        };
+        match injection.kind {
+            InjectionKind::Style => {
+                content_manager.add_style_sheet(&webkit::UserStyleSheet::new(
+                    &source,
+                    frames,
+                    webkit::UserStyleLevel::User,
+                    &[],
+                    &[],
+                ));
+            }
+            InjectionKind::Script => {
+                let time = match injection.time {
+                    InjectionTime::Start => webkit::UserScriptInjectionTime::Start,
+                    InjectionTime::End => webkit::UserScriptInjectionTime::End,
+                };
+                content_manager.add_script(&webkit::UserScript::new(
+                    &source,
+                    frames,
+                    time,
+                    &[],
+                    &[],
+                ));
+            }
+        }
+    }
+}
@@ -23,9 +23,22 @@ mod app_row;
 mod app_window;
 mod application;
 mod apps;
+mod bundle;
+mod clear_data_dialog;
+mod cli;
 mod config;
+mod content_filter;
 mod create_app_dialog;
+mod download_row;
 mod home_page;
+mod icon_editor_dialog;
+mod injection_dialog;
+mod injections;
+mod permissions;
+mod popup_window;
+mod profiles;
+mod scope;
+mod store;
 mod util;
 mod window;
 
@@ -49,6 +62,16 @@ fn main() -> glib::ExitCode {
         .expect("Could not load resources");
     gio::resources_register(&resources);
 
+    // The data directories are named after the program and are needed before the
+    // application runs, so the name GApplication would pick is set right away
+    if let Some(name) = std::env::args()
+        .next()
+        .as_deref()
+        .and_then(|x| std::path::Path::new(x).file_name())
+    {
+        glib::set_prgname(Some(name.to_string_lossy().as_ref()));
+    }
+
     // Create a new GtkApplication. The application manages our main loop,
     // application windows, integration with the window manager/compositor, and
     // desktop features such as file opening and single-instance applications.
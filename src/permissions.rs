@@ -0,0 +1,112 @@
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use webkit::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Camera,
+    Microphone,
+    Geolocation,
+    Notifications,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 4] = [
+        Permission::Camera,
+        Permission::Microphone,
+        Permission::Geolocation,
+        Permission::Notifications,
+    ];
+
+    /// Key used to store the decision in the app's settings
+    pub fn settings_key(&self) -> String {
+        let name = match self {
+            Permission::Camera => "camera",
+            Permission::Microphone => "microphone",
+            Permission::Geolocation => "geolocation",
+            Permission::Notifications => "notifications",
+        };
+        format!("permission{name}")
+    }
+
+    /// What the app is asking to do, split so requests with the same verb can share it
+    fn description(&self) -> (&'static str, &'static str) {
+        match self {
+            Permission::Camera => ("use your", "camera"),
+            Permission::Microphone => ("use your", "microphone"),
+            Permission::Geolocation => ("know your", "location"),
+            Permission::Notifications => ("show", "notifications"),
+        }
+    }
+
+    /// All of the permissions that a request needs to be granted
+    /// Returns an empty list for requests that aren't supported
+    pub fn from_request(request: &webkit::PermissionRequest) -> Vec<Permission> {
+        if let Some(request) = request.downcast_ref::<webkit::UserMediaPermissionRequest>() {
+            let mut permissions = Vec::new();
+            if request.is_for_video_device() {
+                permissions.push(Permission::Camera);
+            }
+            if request.is_for_audio_device() {
+                permissions.push(Permission::Microphone);
+            }
+            permissions
+        } else if request.is::<webkit::GeolocationPermissionRequest>() {
+            vec![Permission::Geolocation]
+        } else if request.is::<webkit::NotificationPermissionRequest>() {
+            vec![Permission::Notifications]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Joins items into a list like "a, b and c"
+fn join_list(items: &[String]) -> String {
+    match items.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+/// Builds a sentence like "use your camera and microphone"
+pub fn describe(permissions: &[Permission]) -> String {
+    let mut phrases: Vec<(&str, Vec<String>)> = Vec::new();
+    for (verb, object) in permissions.iter().map(|x| x.description()) {
+        match phrases.iter_mut().find(|(x, _)| *x == verb) {
+            Some((_, objects)) => objects.push(object.to_string()),
+            None => phrases.push((verb, vec![object.to_string()])),
+        }
+    }
+    let phrases: Vec<String> = phrases
+        .iter()
+        .map(|(verb, objects)| format!("{verb} {}", join_list(objects)))
+        .collect();
+    join_list(&phrases)
+}
+
+/// Merges the decisions of several permissions into one
+/// Any denial wins, otherwise the request is only decided once all are allowed
+pub fn decide(decisions: &[Option<bool>]) -> Option<bool> {
+    if decisions.contains(&Some(false)) {
+        Some(false)
+    } else if decisions.iter().all(|x| *x == Some(true)) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+pub fn request_decided(request: &webkit::PermissionRequest, allowed: bool) {
+    if allowed {
+        request.allow();
+    } else {
+        request.deny();
+    }
+}
+
+pub fn parse_decision(value: &str) -> Option<bool> {
+    value.parse::<bool>().ok()
+}
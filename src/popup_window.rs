@@ -0,0 +1,257 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::clone;
+use gtk::glib;
+use std::cell::RefCell;
+use webkit::prelude::*;
+use webkit::{PolicyDecisionType, WebView};
+
+use crate::app_window::AppWindow;
+use crate::scope;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/zaedus/spider/popup_window.ui")]
+    pub struct PopupWindow {
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        pub progress_bar: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub webview_container: TemplateChild<adw::Bin>,
+
+        pub webview: RefCell<webkit::WebView>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PopupWindow {
+        const NAME: &'static str = "PopupWindow";
+        type Type = super::PopupWindow;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PopupWindow {}
+    impl WidgetImpl for PopupWindow {}
+    impl WindowImpl for PopupWindow {}
+    impl AdwWindowImpl for PopupWindow {}
+
+    impl PopupWindow {
+        pub fn setup_webview(&self, related: &WebView, allowed_hosts: Vec<String>) {
+            // A related view shares the opener's process, network session and settings,
+            // which is what lets the popup message its opener and keep its logins
+            let webview = WebView::builder().related_view(related).build();
+
+            webview.connect_ready_to_show(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |webview| {
+                    _self.load_window_properties(webview);
+                    _self.obj().present();
+                }
+            ));
+            // Pages that never get ready to show would leave a hidden window behind,
+            // so the popup shows up once loaded or goes away if loading fails first
+            webview.connect_load_changed(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_, event| {
+                    if event == webkit::LoadEvent::Finished && !_self.obj().is_visible() {
+                        _self.obj().present();
+                    }
+                }
+            ));
+            webview.connect_load_failed(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[upgrade_or]
+                false,
+                move |_, _, _, _| {
+                    if !_self.obj().is_visible() {
+                        _self.obj().destroy();
+                        return true;
+                    }
+                    false
+                }
+            ));
+            // Sent when the page calls window.close()
+            webview.connect_close(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |_| {
+                    _self.obj().destroy();
+                }
+            ));
+            webview.connect_permission_request(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                #[upgrade_or]
+                false,
+                move |webview, request| {
+                    let Some(app_window) = _self.app_window() else {
+                        return false;
+                    };
+                    app_window.handle_permission_request(webview, request);
+                    true
+                }
+            ));
+
+            webview.connect_title_notify(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |webview| {
+                    let title = webview.title().unwrap_or_default();
+                    _self.window_title.set_title(title.as_str());
+                    _self.obj().set_title(Some(title.as_str()));
+                }
+            ));
+            // Always show where the popup is, sign in forms shouldn't be anonymous
+            webview.connect_uri_notify(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |webview| {
+                    let host = webview
+                        .uri()
+                        .and_then(|x| url::Url::parse(&x).ok())
+                        .and_then(|x| x.host_str().map(|x| x.to_string()))
+                        .unwrap_or_default();
+                    _self.window_title.set_subtitle(host.as_str());
+                }
+            ));
+            webview.connect_estimated_load_progress_notify(clone!(
+                #[weak(rename_to=_self)]
+                self,
+                move |webview| {
+                    let progress = webview.estimated_load_progress();
+                    _self
+                        .progress_bar
+                        .set_fraction(if progress == 1.0 { 0.0 } else { progress });
+                }
+            ));
+
+            handle_popups(&webview, allowed_hosts);
+            // Downloads themselves reach the app's window through the shared network session
+            handle_downloads(&webview);
+
+            self.webview_container.set_child(Some(&webview));
+            self.webview.replace(webview);
+        }
+
+        /// The window of the app that opened the popup, maybe through other popups
+        fn app_window(&self) -> Option<AppWindow> {
+            let mut window = self.obj().transient_for();
+            while let Some(current) = window {
+                if let Some(app_window) = current.downcast_ref::<AppWindow>() {
+                    return Some(app_window.clone());
+                }
+                window = current.transient_for();
+            }
+            None
+        }
+
+        fn load_window_properties(&self, webview: &WebView) {
+            let Some(properties) = webview.window_properties() else {
+                return;
+            };
+            let geometry = properties.geometry();
+            if geometry.width() > 0 && geometry.height() > 0 {
+                self.obj()
+                    .set_default_size(geometry.width(), geometry.height());
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct PopupWindow(ObjectSubclass<imp::PopupWindow>)
+        @extends adw::Window, gtk::Window, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl PopupWindow {
+    pub fn new(parent: &gtk::Window, related: &WebView, allowed_hosts: Vec<String>) -> Self {
+        let obj: Self = glib::Object::builder()
+            .property("transient-for", parent)
+            .property("destroy-with-parent", true)
+            .property("application", parent.application())
+            .build();
+        obj.set_widget_name(parent.widget_name().as_str());
+        obj.imp().setup_webview(related, allowed_hosts);
+        obj
+    }
+
+    pub fn webview(&self) -> WebView {
+        self.imp().webview.borrow().clone()
+    }
+}
+
+/// Opens the popups a page asks for in a `PopupWindow` when they belong to the app
+/// and sends the rest to the default browser
+pub fn handle_popups(webview: &WebView, allowed_hosts: Vec<String>) {
+    webview.connect_decide_policy(clone!(
+        #[strong]
+        allowed_hosts,
+        move |_, decision, decision_type| {
+            if decision_type != PolicyDecisionType::NewWindowAction {
+                return false;
+            }
+            if let Some(uri) = decision
+                .clone()
+                .downcast::<webkit::NavigationPolicyDecision>()
+                .ok()
+                .and_then(|x| x.navigation_action())
+                .and_then(|mut x| x.request())
+                .and_then(|x| x.uri())
+            {
+                if !scope::is_in_app_popup(&uri, &allowed_hosts) {
+                    open::that_detached(uri).unwrap();
+                    decision.ignore();
+                    return true;
+                }
+            }
+            decision.use_();
+            true
+        }
+    ));
+
+    webview.connect_create(move |webview, _| {
+        let parent = webview.root().and_downcast::<gtk::Window>()?;
+        let popup = PopupWindow::new(&parent, webview, allowed_hosts.clone());
+        Some(popup.webview().upcast())
+    });
+}
+
+/// Downloads the responses the webview can't show instead of leaving them blank
+pub fn handle_downloads(webview: &WebView) {
+    webview.connect_decide_policy(|webview, decision, decision_type| {
+        if decision_type != PolicyDecisionType::Response {
+            return false;
+        }
+        let Some(headers) = decision
+            .clone()
+            .downcast::<webkit::ResponsePolicyDecision>()
+            .ok()
+            .and_then(|x| x.response())
+            .and_then(|x| x.http_headers())
+        else {
+            return false;
+        };
+        if headers
+            .one("Content-Type")
+            .is_some_and(|x| webview.can_show_mime_type(x.as_str()))
+        {
+            return false;
+        }
+        decision.download();
+        true
+    });
+}
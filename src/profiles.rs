@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use gtk::glib;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::apps::{
+    app_cache_dir, app_data_dir, copy_dir_contents, get_app_details, AppDetails, ProxyMode,
+};
+use crate::content_filter::{COMPILED_FILTERS_DIR, FILTERS_DIR};
+use crate::injections::INJECTIONS_DIR;
+use crate::store::STORE_FILE;
+
+/// The profile every app starts with, its data lives directly in the app's directories
+pub const DEFAULT_PROFILE: &str = "default";
+const PROFILES_DIR: &str = "profiles";
+const PROFILE_ID_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+}
+
+impl Profile {
+    pub fn default_profile() -> Self {
+        Self {
+            id: DEFAULT_PROFILE.to_string(),
+            name: "Default".to_string(),
+        }
+    }
+}
+
+/// Encodes profiles as a comma separated list of `id:name` for the app's settings
+pub fn profiles_to_string(profiles: &[Profile]) -> String {
+    profiles
+        .iter()
+        .map(|x| {
+            format!(
+                "{}:{}",
+                x.id,
+                glib::Uri::escape_string(&x.name, None, false)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Ids end up in paths, so anything but plain alphanumeric ids is dropped
+pub fn is_valid_profile_id(id: &str) -> bool {
+    !id.is_empty() && id != DEFAULT_PROFILE && id.chars().all(|x| x.is_ascii_alphanumeric())
+}
+
+pub fn parse_profiles(value: &str) -> Vec<Profile> {
+    value
+        .split(',')
+        .filter_map(|x| x.split_once(':'))
+        .filter(|(id, _)| is_valid_profile_id(id))
+        .filter_map(|(id, name)| {
+            Some(Profile {
+                id: id.to_string(),
+                name: glib::Uri::unescape_string(name, None::<&str>)?.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn profile_data_dir(id: &str, profile: &str) -> PathBuf {
+    if profile == DEFAULT_PROFILE {
+        app_data_dir(id)
+    } else {
+        app_data_dir(id).join(PROFILES_DIR).join(profile)
+    }
+}
+
+pub fn profile_cache_dir(id: &str, profile: &str) -> PathBuf {
+    if profile == DEFAULT_PROFILE {
+        app_cache_dir(id)
+    } else {
+        app_cache_dir(id).join(PROFILES_DIR).join(profile)
+    }
+}
+
+fn gen_profile_id(details: &AppDetails) -> String {
+    loop {
+        let id: String = rand::thread_rng()
+            .sample_iter(rand::distributions::Uniform::from('a'..='z'))
+            .take(PROFILE_ID_LENGTH)
+            .collect();
+        if !details.profiles.iter().any(|x| x.id == id) {
+            return id;
+        }
+    }
+}
+
+/// Loads the stored details, applies a change to the profiles and saves them
+/// Everything else is left as is so it doesn't clobber unrelated settings
+fn update_profiles(
+    id: &str,
+    update: impl FnOnce(&mut AppDetails) -> anyhow::Result<()>,
+) -> anyhow::Result<AppDetails> {
+    let Some(mut details) = get_app_details(id) else {
+        bail!("No app with id {id}");
+    };
+    update(&mut details)?;
+    details.save()?;
+    Ok(details)
+}
+
+pub fn add_profile(id: &str) -> anyhow::Result<AppDetails> {
+    update_profiles(id, |details| {
+        let name = format!("Profile {}", details.profiles.len() + 1);
+        details.profiles.push(Profile {
+            id: gen_profile_id(details),
+            name,
+        });
+        Ok(())
+    })
+}
+
+pub fn rename_profile(id: &str, profile: &str, name: &str) -> anyhow::Result<AppDetails> {
+    update_profiles(id, |details| {
+        if name.trim().is_empty() {
+            bail!("Profile name can't be empty");
+        }
+        if let Some(profile) = details.profiles.iter_mut().find(|x| x.id == profile) {
+            profile.name = name.trim().to_string();
+        }
+        Ok(())
+    })
+}
+
+pub fn copy_profile(id: &str, profile: &str) -> anyhow::Result<AppDetails> {
+    update_profiles(id, |details| copy_profile_dirs(details, profile))
+}
+
+/// Adds a copy of the profile, the default one shares its directories with what belongs
+/// to the whole app so that is left out
+fn copy_profile_dirs(details: &mut AppDetails, profile: &str) -> anyhow::Result<()> {
+    let Some(source) = details.all_profiles().into_iter().find(|x| x.id == profile) else {
+        bail!("No profile with id {profile}");
+    };
+    let copy = Profile {
+        id: gen_profile_id(details),
+        name: format!("{} (Copy)", source.name),
+    };
+    copy_dir_contents(
+        &profile_data_dir(&details.id, &source.id),
+        &profile_data_dir(&details.id, &copy.id),
+        &[PROFILES_DIR, INJECTIONS_DIR, FILTERS_DIR, STORE_FILE],
+    )?;
+    copy_dir_contents(
+        &profile_cache_dir(&details.id, &source.id),
+        &profile_cache_dir(&details.id, &copy.id),
+        &[PROFILES_DIR, COMPILED_FILTERS_DIR],
+    )?;
+    details.profiles.push(copy);
+    Ok(())
+}
+
+pub fn delete_profile(id: &str, profile: &str) -> anyhow::Result<AppDetails> {
+    if profile == DEFAULT_PROFILE {
+        bail!("The default profile can't be deleted");
+    }
+    update_profiles(id, |details| {
+        details.profiles.retain(|x| x.id != profile);
+        if details.profile == profile {
+            details.profile = DEFAULT_PROFILE.to_string();
+        }
+        for dir in [
+            profile_data_dir(id, profile),
+            profile_cache_dir(id, profile),
+        ] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Builds the network session of the app's current profile
+/// This is where webkit keeps the profile's cache, data, and cookies
+pub fn build_network_session(details: &AppDetails) -> webkit::NetworkSession {
+    let data_dir = profile_data_dir(&details.id, &details.profile);
+    let cache_dir = profile_cache_dir(&details.id, &details.profile);
+    std::fs::create_dir_all(data_dir.clone()).unwrap();
+    std::fs::create_dir_all(cache_dir.clone()).unwrap();
+
+    let network_session = webkit::NetworkSession::builder()
+        .cache_directory(cache_dir.to_str().unwrap())
+        .data_directory(data_dir.join("data").to_str().unwrap())
+        .build();
+
+    match &details.proxy {
+        ProxyMode::System => (),
+        ProxyMode::None => {
+            network_session.set_proxy_settings(webkit::NetworkProxyMode::NoProxy, None)
+        }
+        ProxyMode::Custom { url, ignore_hosts } => {
+            let ignore_hosts: Vec<&str> = ignore_hosts.iter().map(|x| x.as_str()).collect();
+            let mut proxy_settings =
+                webkit::NetworkProxySettings::new(Some(url.as_str()), &ignore_hosts);
+            network_session
+                .set_proxy_settings(webkit::NetworkProxyMode::Custom, Some(&mut proxy_settings));
+        }
+    }
+
+    let cookie_manager = network_session.cookie_manager().unwrap();
+    cookie_manager.set_persistent_storage(
+        data_dir.join("cookie").to_str().unwrap(),
+        webkit::CookiePersistentStorage::Sqlite,
+    );
+
+    network_session
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,93 @@
+use std::sync::Once;
+
+use super::*;
+
+/// Points the app directories at a temporary directory, they are read once per process
+fn init_dirs() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let root = std::env::temp_dir().join(format!("spider-tests-{}", std::process::id()));
+        std::env::set_var("XDG_DATA_HOME", root.join("data"));
+        std::env::set_var("XDG_CACHE_HOME", root.join("cache"));
+        glib::set_prgname(Some("spider"));
+    });
+}
+
+fn app(id: &str) -> AppDetails {
+    init_dirs();
+    AppDetails {
+        id: id.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn copy_default_profile() {
+    let mut details = app("copydefault");
+    let data_dir = profile_data_dir(&details.id, DEFAULT_PROFILE);
+    std::fs::create_dir_all(data_dir.join("data")).unwrap();
+    std::fs::write(data_dir.join("cookie"), "cookies").unwrap();
+    std::fs::write(data_dir.join(STORE_FILE), "{}").unwrap();
+    std::fs::create_dir_all(data_dir.join(INJECTIONS_DIR)).unwrap();
+    std::fs::create_dir_all(data_dir.join(FILTERS_DIR)).unwrap();
+    std::fs::create_dir_all(data_dir.join(PROFILES_DIR).join("other")).unwrap();
+    std::fs::create_dir_all(profile_cache_dir(&details.id, DEFAULT_PROFILE)).unwrap();
+    std::fs::write(
+        profile_cache_dir(&details.id, DEFAULT_PROFILE).join("cached"),
+        "",
+    )
+    .unwrap();
+    std::fs::create_dir_all(
+        profile_cache_dir(&details.id, DEFAULT_PROFILE).join(COMPILED_FILTERS_DIR),
+    )
+    .unwrap();
+
+    copy_profile_dirs(&mut details, DEFAULT_PROFILE).unwrap();
+    let copy = details.profiles.last().unwrap();
+    assert_eq!(copy.name, "Default (Copy)");
+
+    let copied = profile_data_dir(&details.id, &copy.id);
+    assert_eq!(
+        std::fs::read_to_string(copied.join("cookie")).unwrap(),
+        "cookies"
+    );
+    assert!(copied.join("data").is_dir());
+    assert!(profile_cache_dir(&details.id, &copy.id)
+        .join("cached")
+        .exists());
+    // What belongs to the whole app stays with the app
+    assert!(!copied.join(STORE_FILE).exists());
+    assert!(!copied.join(INJECTIONS_DIR).exists());
+    assert!(!copied.join(FILTERS_DIR).exists());
+    assert!(!copied.join(PROFILES_DIR).exists());
+    assert!(!profile_cache_dir(&details.id, &copy.id)
+        .join(COMPILED_FILTERS_DIR)
+        .exists());
+}
+
+#[test]
+fn copy_named_profile() {
+    let mut details = app("copynamed");
+    details.profiles.push(Profile {
+        id: "work".to_string(),
+        name: "Work".to_string(),
+    });
+    let data_dir = profile_data_dir(&details.id, "work");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(data_dir.join("cookie"), "work cookies").unwrap();
+
+    copy_profile_dirs(&mut details, "work").unwrap();
+    let copy = details.profiles.last().unwrap();
+    assert_eq!(copy.name, "Work (Copy)");
+    assert_eq!(
+        std::fs::read_to_string(profile_data_dir(&details.id, &copy.id).join("cookie")).unwrap(),
+        "work cookies"
+    );
+}
+
+#[test]
+fn copy_missing_profile() {
+    let mut details = app("copymissing");
+    assert!(copy_profile_dirs(&mut details, "nothere").is_err());
+    assert!(details.profiles.is_empty());
+}
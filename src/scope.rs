@@ -0,0 +1,70 @@
+use url::Url;
+
+/// Hosts used by common "sign in with" flows
+/// Navigating to these keeps the user inside the app so the login can
+/// redirect back to it afterwards
+const AUTH_PROVIDERS: &[&str] = &[
+    "accounts.google.com",
+    "login.microsoftonline.com",
+    "login.live.com",
+    "appleid.apple.com",
+    "*.okta.com",
+    "*.auth0.com",
+    "login.salesforce.com",
+];
+
+fn host_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|x| x.host_str().map(|x| x.to_lowercase()))
+}
+
+/// Checks a host against a pattern
+/// Patterns are either an exact host, a wildcard of the form `*.example.com`
+/// (which also matches `example.com`) or `*` to match every host
+pub fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    if pattern == "*" {
+        return true;
+    }
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        host == domain || host.ends_with(format!(".{domain}").as_str())
+    } else {
+        host == pattern
+    }
+}
+
+pub fn default_scope(url: &str) -> Vec<String> {
+    host_of(url).into_iter().collect()
+}
+
+pub fn parse_scope(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|x| x.trim().to_lowercase())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// Whether the url is allowed to be opened inside of the app
+/// Urls without a host (about:blank, data:, etc.) are always in scope
+pub fn is_in_scope(url: &str, patterns: &[String]) -> bool {
+    match host_of(url) {
+        Some(host) => patterns.iter().any(|x| host_matches(&host, x)),
+        None => true,
+    }
+}
+
+pub fn is_auth_provider(url: &str) -> bool {
+    host_of(url).is_some_and(|host| AUTH_PROVIDERS.iter().any(|x| host_matches(&host, x)))
+}
+
+pub fn is_same_host(a: &str, b: &str) -> bool {
+    host_of(a).is_some_and(|x| Some(x) == host_of(b))
+}
+
+/// Whether a popup opened by the page should stay inside of the app
+/// Sign in popups have to, since they message the page that opened them once done
+pub fn is_in_app_popup(url: &str, patterns: &[String]) -> bool {
+    is_in_scope(url, patterns) || is_auth_provider(url)
+}
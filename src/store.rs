@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::application::settings;
+use crate::apps::{AppDetails, AppsSettings};
+use crate::profiles::DEFAULT_PROFILE;
+
+/// Version of the per-app settings files, bumped whenever a field changes meaning
+/// Fields that are only added don't need a bump, they fall back to their defaults
+pub const STORE_VERSION: u32 = 1;
+/// Name of the settings file in the app's data directory
+pub const STORE_FILE: &str = "app.json";
+
+/// Where the settings of each app are kept
+/// The list of app ids stays in GSettings, it is needed before the data directory is known
+pub trait AppStore {
+    fn load(&self, id: &str) -> anyhow::Result<AppDetails>;
+    fn save(&self, details: &AppDetails) -> anyhow::Result<()>;
+    fn delete(&self, id: &str) -> anyhow::Result<()>;
+}
+
+/// The original storage, every app as a map of strings in the `apps-settings` key
+/// Only read to migrate to files, and for apps that couldn't be migrated yet
+pub struct GSettingsStore;
+
+impl AppStore for GSettingsStore {
+    fn load(&self, id: &str) -> anyhow::Result<AppDetails> {
+        let apps_settings = settings().get::<AppsSettings>("apps-settings");
+        let app_settings = apps_settings
+            .get(id)
+            .ok_or(anyhow!("No settings for app {id}"))?;
+        AppDetails::from_hashmap(id, app_settings).ok_or(anyhow!("Url and title are required"))
+    }
+
+    fn save(&self, details: &AppDetails) -> anyhow::Result<()> {
+        let settings = settings();
+        let mut apps_settings = settings.get::<AppsSettings>("apps-settings");
+        apps_settings.insert(details.id.clone(), details.to_hashmap());
+        settings.set("apps-settings", apps_settings)?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> anyhow::Result<()> {
+        let settings = settings();
+        let mut apps_settings = settings.get::<AppsSettings>("apps-settings");
+        if apps_settings.remove(id).is_some() {
+            settings.set("apps-settings", apps_settings)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AppDocument {
+    version: u32,
+    details: AppDetails,
+}
+
+/// The settings of an app as they are written to its file and to bundles
+pub fn to_document(details: &AppDetails) -> serde_json::Result<serde_json::Value> {
+    serde_json::to_value(AppDocument {
+        version: STORE_VERSION,
+        details: details.clone(),
+    })
+}
+
+/// Reads settings written by `to_document`, replacing values that no longer make sense
+pub fn from_document(id: &str, document: serde_json::Value) -> anyhow::Result<AppDetails> {
+    let version = document
+        .get("version")
+        .and_then(|x| x.as_u64())
+        .ok_or(anyhow!("Settings of app {id} have no version"))?;
+    // Saving would drop whatever the newer version added
+    if version > STORE_VERSION as u64 {
+        bail!("Settings of app {id} were written by a newer version of Spider");
+    }
+
+    let mut details = serde_json::from_value::<AppDocument>(document)?.details;
+    // The id is what the app is known by, even if the settings were copied over
+    details.id = id.to_string();
+    if !details
+        .all_profiles()
+        .iter()
+        .any(|x| x.id == details.profile)
+    {
+        details.profile = DEFAULT_PROFILE.to_string();
+    }
+    if details.zoom_level <= 0.0 {
+        details.zoom_level = 1.0;
+    }
+    Ok(details)
+}
+
+/// A JSON document per app, next to the app's WebKit data
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.root.join(id).join(STORE_FILE)
+    }
+}
+
+impl AppStore for FileStore {
+    fn load(&self, id: &str) -> anyhow::Result<AppDetails> {
+        from_document(
+            id,
+            serde_json::from_str(&std::fs::read_to_string(self.path(id))?)?,
+        )
+    }
+
+    fn save(&self, details: &AppDetails) -> anyhow::Result<()> {
+        let path = self.path(&details.id);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        // Written next to the file first so a crash can't leave half of it behind
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(
+            &temp_path,
+            serde_json::to_string_pretty(&to_document(details)?)?,
+        )?;
+        std::fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> anyhow::Result<()> {
+        match std::fs::remove_file(self.path(id)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Moves the apps out of the `apps-settings` key into their own files
+/// Apps that fail stay in the old key and are tried again on the next start,
+/// the key is only cleared once every app made it so nothing is lost to a broken entry
+pub fn migrate(to: &impl AppStore) {
+    let settings = settings();
+    if settings.boolean("apps-migrated") {
+        return;
+    }
+
+    if !migrate_apps(&settings.get::<Vec<String>>("app-ids"), &GSettingsStore, to) {
+        return;
+    }
+    settings.reset("apps-settings");
+    if let Err(err) = settings.set_boolean("apps-migrated", true) {
+        eprintln!("Failed to save the migration: {err}");
+    }
+}
+
+/// Copies the apps that `to` doesn't have yet from `from`
+/// Returns whether every app made it, only then can `from` be cleared
+fn migrate_apps(ids: &[String], from: &impl AppStore, to: &impl AppStore) -> bool {
+    let mut migrated_all = true;
+    for id in ids {
+        // Apps that made it on an earlier attempt may have changed since
+        if to.load(id).is_ok() {
+            continue;
+        }
+        if let Err(err) = from.load(id).and_then(|details| to.save(&details)) {
+            eprintln!("Failed to migrate the settings of app {id}: {err}");
+            migrated_all = false;
+        }
+    }
+    migrated_all
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gtk::glib;
+
+use super::*;
+use crate::profiles::Profile;
+
+/// A store that fails to load the apps it doesn't have, like a missing file
+#[derive(Default)]
+struct MemoryStore {
+    apps: RefCell<HashMap<String, AppDetails>>,
+}
+
+impl MemoryStore {
+    fn with_apps(apps: &[AppDetails]) -> Self {
+        let store = Self::default();
+        for details in apps {
+            store.save(details).unwrap();
+        }
+        store
+    }
+}
+
+impl AppStore for MemoryStore {
+    fn load(&self, id: &str) -> anyhow::Result<AppDetails> {
+        self.apps
+            .borrow()
+            .get(id)
+            .cloned()
+            .ok_or(anyhow!("No app {id}"))
+    }
+
+    fn save(&self, details: &AppDetails) -> anyhow::Result<()> {
+        self.apps
+            .borrow_mut()
+            .insert(details.id.clone(), details.clone());
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> anyhow::Result<()> {
+        self.apps.borrow_mut().remove(id);
+        Ok(())
+    }
+}
+
+fn file_store() -> FileStore {
+    FileStore::new(glib::mkdtemp(glib::tmp_dir().join("spider-store-XXXXXX")).unwrap())
+}
+
+fn app(id: &str, title: &str) -> AppDetails {
+    AppDetails::new(
+        id.to_string(),
+        title.to_string(),
+        "https://example.com".to_string(),
+    )
+}
+
+fn ids(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|x| x.to_string()).collect()
+}
+
+#[test]
+fn file_store_round_trip() {
+    let store = file_store();
+    let mut details = app("roundtrip", "Round Trip");
+    details.zoom_level = 1.5;
+    details.theme_color = Some("#336699".to_string());
+    details.profiles.push(Profile {
+        id: "work".to_string(),
+        name: "Work".to_string(),
+    });
+    details.profile = "work".to_string();
+
+    store.save(&details).unwrap();
+    assert_eq!(store.load("roundtrip").unwrap(), details);
+
+    store.delete("roundtrip").unwrap();
+    assert!(store.load("roundtrip").is_err());
+    // Deleting twice is fine
+    store.delete("roundtrip").unwrap();
+}
+
+#[test]
+fn file_store_rejects_unknown_versions() {
+    let store = file_store();
+    let details = app("versions", "Versions");
+    store.save(&details).unwrap();
+    let path = store.path("versions");
+
+    let mut document = to_document(&details).unwrap();
+    document["version"] = (STORE_VERSION + 1).into();
+    std::fs::write(&path, document.to_string()).unwrap();
+    assert!(store.load("versions").is_err());
+
+    document.as_object_mut().unwrap().remove("version");
+    std::fs::write(&path, document.to_string()).unwrap();
+    assert!(store.load("versions").is_err());
+}
+
+#[test]
+fn file_store_replaces_invalid_values() {
+    let store = file_store();
+    let mut details = app("sanitise", "Sanitise");
+    details.profile = "removed".to_string();
+    details.zoom_level = 0.0;
+    store.save(&details).unwrap();
+
+    let loaded = store.load("sanitise").unwrap();
+    assert_eq!(loaded.profile, DEFAULT_PROFILE);
+    assert_eq!(loaded.zoom_level, 1.0);
+}
+
+#[test]
+fn failed_migration_keeps_old_settings() {
+    let from = MemoryStore::with_apps(&[app("a", "A")]);
+    let to = MemoryStore::default();
+
+    assert!(!migrate_apps(&ids(&["a", "broken"]), &from, &to));
+    assert_eq!(to.load("a").unwrap().title, "A");
+    assert!(to.load("broken").is_err());
+    assert!(from.load("a").is_ok());
+}
+
+#[test]
+fn retried_migration_skips_migrated_apps() {
+    let from = MemoryStore::with_apps(&[app("a", "A"), app("b", "B")]);
+    // Changed since it was migrated on an earlier attempt
+    let to = MemoryStore::with_apps(&[app("a", "Renamed")]);
+
+    assert!(migrate_apps(&ids(&["a", "b"]), &from, &to));
+    assert_eq!(to.load("a").unwrap().title, "Renamed");
+    assert_eq!(to.load("b").unwrap().title, "B");
+}
@@ -1,18 +1,60 @@
-use anyhow::bail;
-use futures::future::join_all;
+use anyhow::{anyhow, bail};
+use futures::future::{join, join_all};
 use gdk_pixbuf::Pixbuf;
 use gtk::{gdk, gio, glib, prelude::*};
 use image::{codecs::png::PngEncoder, ImageEncoder, ImageFormat};
 use isahc::{config, prelude::*};
 use lazy_static::lazy_static;
 use scraper::{Html, Selector};
+use serde::Deserialize;
 use std::{collections::HashSet, path::Path};
 use url::Url;
 
+/// Icons larger than this are left out, they are scaled down for the launcher anyway
+const MAX_ICON_SIZE: u32 = 256;
+const SVG_MIME_TYPE: &str = "image/svg+xml";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
 #[derive(Debug)]
 pub struct WebsiteMeta {
     pub icon: Option<Image>,
     pub title: Option<String>,
+    pub start_url: Option<Url>,
+    pub scope: Option<Url>,
+    pub theme_color: Option<String>,
+    pub background_color: Option<String>,
+}
+
+/// The parts of a web app manifest that Spider uses
+/// https://developer.mozilla.org/en-US/docs/Web/Manifest
+#[derive(Debug, Default, Deserialize)]
+struct WebManifest {
+    name: Option<String>,
+    short_name: Option<String>,
+    start_url: Option<String>,
+    scope: Option<String>,
+    theme_color: Option<String>,
+    background_color: Option<String>,
+    #[serde(default)]
+    icons: Vec<ManifestIcon>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: Option<String>,
+    #[serde(rename = "type")]
+    mime_type: Option<String>,
+    purpose: Option<String>,
+}
+
+impl ManifestIcon {
+    // Maskable and monochrome icons don't look right as a launcher icon
+    fn is_usable(&self) -> bool {
+        self.purpose
+            .as_ref()
+            .is_none_or(|x| x.split_whitespace().any(|x| x == "any"))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -28,6 +70,90 @@ impl ImageSize {
             ImageSize::Sized((w, _)) => *w,
         }
     }
+
+    fn fits(&self) -> bool {
+        match self {
+            ImageSize::Variable => true,
+            ImageSize::Sized((w, h)) => *w.max(h) <= MAX_ICON_SIZE,
+        }
+    }
+}
+
+/// Picks the size to expect from a `sizes` attribute like "16x16 32x32"
+/// ICO files list every resolution they hold, the largest that fits is the one used
+fn parse_sizes(sizes: &str) -> Option<ImageSize> {
+    let sizes: Vec<ImageSize> = sizes
+        .split_whitespace()
+        .filter_map(|x| {
+            let x = x.to_ascii_lowercase();
+            let (w, h) = x.split_once('x')?;
+            Some(ImageSize::Sized((w.parse().ok()?, h.parse().ok()?)))
+        })
+        .collect();
+    sizes
+        .iter()
+        .filter(|x| x.fits())
+        .max()
+        .or_else(|| sizes.iter().min())
+        .cloned()
+}
+
+/// An icon that a page or its manifest links to, along with what it declares about itself
+#[derive(Debug, Clone)]
+struct IconCandidate {
+    url: Url,
+    /// Taken from the `sizes` attribute, icons without one have to be downloaded to know
+    size: Option<ImageSize>,
+    is_svg: bool,
+}
+
+impl IconCandidate {
+    fn new(url: Url, sizes: Option<&str>, mime_type: Option<&str>) -> Self {
+        let is_svg = mime_type.is_some_and(|x| x == SVG_MIME_TYPE)
+            || Path::new(url.path())
+                .extension()
+                .is_some_and(|x| x == "svg");
+        Self {
+            size: sizes.filter(|_| !is_svg).and_then(parse_sizes),
+            is_svg,
+            url,
+        }
+    }
+}
+
+/// ICO files hold several resolutions and the image crate prefers color depth over size,
+/// so the largest frame is cut out on its own
+fn largest_ico_frame(buffer: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let count = buffer
+        .get(4..6)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .unwrap_or(0) as usize;
+    let entry = (0..count)
+        .filter_map(|i| buffer.get(6 + i * 16..22 + i * 16))
+        .max_by_key(|entry| {
+            // A width or height of 0 stands for 256
+            let side = |x: u8| if x == 0 { 256 } else { x as u32 };
+            (
+                side(entry[0]).min(side(entry[1])),
+                u16::from_le_bytes([entry[6], entry[7]]),
+            )
+        })
+        .ok_or(anyhow!("Icon file has no images"))?;
+    let length = u32::from_le_bytes(entry[8..12].try_into()?) as usize;
+    let offset = u32::from_le_bytes(entry[12..16].try_into()?) as usize;
+    let data = buffer
+        .get(offset..offset + length)
+        .ok_or(anyhow!("Icon file is cut off"))?;
+    if data.starts_with(PNG_SIGNATURE) {
+        return Ok(data.to_vec());
+    }
+
+    // Bitmap frames can't be read without the directory, so they get one listing only them
+    let mut frame = vec![0, 0, 1, 0, 1, 0];
+    frame.extend_from_slice(&entry[..12]);
+    frame.extend_from_slice(&22u32.to_le_bytes());
+    frame.extend_from_slice(data);
+    Ok(frame)
 }
 
 impl PartialOrd for ImageSize {
@@ -127,37 +253,352 @@ impl Ord for Image {
 impl Image {
     pub fn from_buffer(buffer: Vec<u8>, is_svg: bool) -> anyhow::Result<Self> {
         if is_svg {
-            Ok(Image {
+            // Error pages can be served under an icon's url too
+            if !String::from_utf8_lossy(&buffer).contains("<svg") {
+                bail!("Not an SVG image");
+            }
+            return Ok(Image {
                 buffer,
                 size: ImageSize::Variable,
-            })
+            });
+        }
+
+        let buffer = if image::guess_format(buffer.as_slice())? == ImageFormat::Ico {
+            largest_ico_frame(&buffer)?
         } else {
-            let format = image::guess_format(buffer.as_slice())?;
-            let image = image::load_from_memory_with_format(buffer.as_slice(), format)?;
-            if image.width() != image.height() {
-                bail!("Image is not square")
-            }
-            let buffer = if format != ImageFormat::Png {
-                let mut encbuf = Vec::new();
-                PngEncoder::new(&mut encbuf).write_image(
-                    image.as_bytes(),
-                    image.width(),
-                    image.height(),
-                    image::ExtendedColorType::Rgba8,
-                )?;
-                encbuf
-            } else {
-                buffer
-            };
-            Ok(Image {
+            buffer
+        };
+        let format = image::guess_format(buffer.as_slice())?;
+        let image = image::load_from_memory_with_format(buffer.as_slice(), format)?;
+        let side = image.width().max(image.height());
+        if format == ImageFormat::Png && image.width() == image.height() {
+            return Ok(Image {
                 buffer,
-                size: ImageSize::Sized((image.width(), image.height())),
-            })
+                size: ImageSize::Sized((side, side)),
+            });
         }
+
+        // Icons that aren't square are centered on a transparent square
+        let mut canvas = image::RgbaImage::new(side, side);
+        image::imageops::overlay(
+            &mut canvas,
+            &image.to_rgba8(),
+            ((side - image.width()) / 2) as i64,
+            ((side - image.height()) / 2) as i64,
+        );
+        let mut encbuf = Vec::new();
+        PngEncoder::new(&mut encbuf).write_image(
+            canvas.as_raw(),
+            side,
+            side,
+            image::ExtendedColorType::Rgba8,
+        )?;
+        Ok(Image {
+            buffer: encbuf,
+            size: ImageSize::Sized((side, side)),
+        })
     }
     pub fn to_gdk_texture(&self, size: i32) -> gdk::Texture {
         to_gdk_texture(&self.buffer, size)
     }
+
+    /// Scales icons above `MAX_ICON_SIZE` down to it, sites that only have large icons
+    /// still get one that way
+    fn fit_to_max_size(self) -> anyhow::Result<Self> {
+        if self.size.fits() {
+            return Ok(self);
+        }
+        let image = image::load_from_memory(&self.buffer)?.resize(
+            MAX_ICON_SIZE,
+            MAX_ICON_SIZE,
+            image::imageops::Lanczos3,
+        );
+        let mut encbuf = Vec::new();
+        PngEncoder::new(&mut encbuf).write_image(
+            image.to_rgba8().as_raw(),
+            image.width(),
+            image.height(),
+            image::ExtendedColorType::Rgba8,
+        )?;
+        Ok(Image {
+            buffer: encbuf,
+            size: ImageSize::Sized((image.width(), image.height())),
+        })
+    }
+}
+
+/// Side of the icons made in the icon editor, the largest size icons are fetched at
+pub const EDITED_ICON_SIZE: u32 = MAX_ICON_SIZE;
+
+/// How the icon editor makes an image square
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconFit {
+    /// Centers the image on a transparent square
+    #[default]
+    Pad,
+    /// Cuts the longer side down to the shorter one
+    Crop,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IconStyle {
+    pub fit: IconFit,
+    /// Painted behind the transparent parts of the image
+    pub background: Option<image::Rgba<u8>>,
+    /// Masks the corners like the rounded squares of GNOME app icons
+    pub rounded: bool,
+    /// Scales small images up by whole pixels first so they don't get blurry
+    pub sharp: bool,
+}
+
+/// Reads an image for the icon editor, SVGs are rendered at the size the editor produces
+pub fn decode_icon(buffer: &[u8], is_svg: bool) -> anyhow::Result<image::DynamicImage> {
+    if is_svg {
+        if !String::from_utf8_lossy(buffer).contains("<svg") {
+            bail!("Not an SVG image");
+        }
+        let bytes = glib::Bytes::from(buffer);
+        let stream = gio::MemoryInputStream::from_bytes(&bytes);
+        let pixbuf = Pixbuf::from_stream_at_scale(
+            &stream,
+            EDITED_ICON_SIZE as i32,
+            EDITED_ICON_SIZE as i32,
+            true,
+            gio::Cancellable::NONE,
+        )?;
+        return Ok(image::load_from_memory_with_format(
+            &pixbuf.save_to_bufferv("png", &[])?,
+            ImageFormat::Png,
+        )?);
+    }
+
+    let buffer = if image::guess_format(buffer)? == ImageFormat::Ico {
+        largest_ico_frame(buffer)?
+    } else {
+        buffer.to_vec()
+    };
+    Ok(image::load_from_memory(&buffer)?)
+}
+
+/// Turns an image into a square PNG of `EDITED_ICON_SIZE` following the style
+pub fn render_icon(source: &image::DynamicImage, style: &IconStyle) -> anyhow::Result<Vec<u8>> {
+    let (width, height) = (source.width(), source.height());
+    if width == 0 || height == 0 {
+        bail!("Image is empty");
+    }
+    let square = match style.fit {
+        IconFit::Crop => {
+            let side = width.min(height);
+            source
+                .crop_imm((width - side) / 2, (height - side) / 2, side, side)
+                .to_rgba8()
+        }
+        IconFit::Pad => {
+            let side = width.max(height);
+            let mut canvas = image::RgbaImage::new(side, side);
+            image::imageops::overlay(
+                &mut canvas,
+                &source.to_rgba8(),
+                ((side - width) / 2) as i64,
+                ((side - height) / 2) as i64,
+            );
+            canvas
+        }
+    };
+
+    let side = square.width();
+    let mut icon = if style.sharp && side < EDITED_ICON_SIZE {
+        // Whole pixel steps keep the edges hard, only the remainder gets smoothed
+        let blocky = side * (EDITED_ICON_SIZE / side);
+        let blocky = image::imageops::resize(&square, blocky, blocky, image::imageops::Nearest);
+        image::imageops::resize(
+            &blocky,
+            EDITED_ICON_SIZE,
+            EDITED_ICON_SIZE,
+            image::imageops::Triangle,
+        )
+    } else {
+        image::imageops::resize(
+            &square,
+            EDITED_ICON_SIZE,
+            EDITED_ICON_SIZE,
+            image::imageops::Lanczos3,
+        )
+    };
+
+    if let Some(color) = style.background {
+        let mut canvas = image::RgbaImage::from_pixel(EDITED_ICON_SIZE, EDITED_ICON_SIZE, color);
+        image::imageops::overlay(&mut canvas, &icon, 0, 0);
+        icon = canvas;
+    }
+    if style.rounded {
+        round_corners(&mut icon);
+    }
+
+    let mut encbuf = Vec::new();
+    PngEncoder::new(&mut encbuf).write_image(
+        icon.as_raw(),
+        EDITED_ICON_SIZE,
+        EDITED_ICON_SIZE,
+        image::ExtendedColorType::Rgba8,
+    )?;
+    Ok(encbuf)
+}
+
+/// Fades out the corners with antialiased edges, the radius is about the one
+/// of the rounded squares in GNOME's app icon template
+fn round_corners(image: &mut image::RgbaImage) {
+    let side = image.width() as f32;
+    let radius = side * 3.0 / 32.0;
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let distance = |position: u32| {
+            let center = position as f32 + 0.5;
+            (radius - center).max(center - (side - radius)).max(0.0)
+        };
+        let (dx, dy) = (distance(x), distance(y));
+        if dx == 0.0 || dy == 0.0 {
+            continue;
+        }
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
+
+/// Accent colors of GNOME, for icons made from a letter when the website has no theme color
+const LETTER_ICON_COLORS: [&str; 8] = [
+    "#3584e4", "#2190a4", "#3a944a", "#c88800", "#ed5b00", "#e62d42", "#d56199", "#9141ac",
+];
+/// Size of a glyph cell in the letter icon, glyphs are 5 by 7 cells
+const LETTER_CELL_SIZE: u32 = 18;
+
+/// Rows of a 5 by 7 glyph for the letters, digits and '?' a letter icon can show,
+/// the lowest 5 bits of each row are its cells from left to right
+fn letter_glyph(letter: char) -> [u8; 7] {
+    match letter {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'A' => [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        _ => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+/// The character a letter icon shows, from the title or else the website's domain
+/// Only ASCII letters and digits have a glyph, so other scripts fall back to the domain
+fn icon_letter(title: &str, url: &Url) -> char {
+    let domain = url.host_str().unwrap_or_default();
+    let domain = domain.strip_prefix("www.").unwrap_or(domain);
+    [title, domain]
+        .into_iter()
+        .filter_map(|x| x.chars().find(|x| x.is_alphanumeric()))
+        .find(|x| x.is_ascii_alphanumeric())
+        .map(|x| x.to_ascii_uppercase())
+        .unwrap_or('?')
+}
+
+/// Picked from the domain so a website always gets the same color
+fn icon_color(url: &Url) -> &'static str {
+    let index = url
+        .host_str()
+        .unwrap_or_default()
+        .bytes()
+        .fold(0usize, |hash, x| {
+            hash.wrapping_mul(31).wrapping_add(x as usize)
+        });
+    LETTER_ICON_COLORS[index % LETTER_ICON_COLORS.len()]
+}
+
+/// An icon for websites without a usable one, the first letter of the title on a
+/// rounded square of the theme color
+/// Drawn from a built in glyph so it works without fonts or image loaders
+pub fn letter_icon(title: &str, url: &Url, theme_color: Option<&str>) -> anyhow::Result<Vec<u8>> {
+    let background = theme_color
+        .and_then(|x| gdk::RGBA::parse(x).ok())
+        .unwrap_or_else(|| gdk::RGBA::parse(icon_color(url)).unwrap());
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let (red, green, blue) = (background.red(), background.green(), background.blue());
+    // Dark letters on light backgrounds so they stay readable
+    let foreground = if 0.2126 * red + 0.7152 * green + 0.0722 * blue > 0.6 {
+        image::Rgba([0x24, 0x1f, 0x31, 255])
+    } else {
+        image::Rgba([255, 255, 255, 255])
+    };
+
+    // The glyph with an empty cell around it so its edges fade out when scaled
+    let mut glyph = image::GrayImage::new(7, 9);
+    for (y, row) in letter_glyph(icon_letter(title, url)).iter().enumerate() {
+        for x in 0..5 {
+            if row & (0x10 >> x) != 0 {
+                glyph.put_pixel(x + 1, y as u32 + 1, image::Luma([255]));
+            }
+        }
+    }
+    // Smooth scaling followed by a threshold turns the cells into rounded strokes
+    let glyph = image::imageops::resize(
+        &glyph,
+        7 * LETTER_CELL_SIZE,
+        9 * LETTER_CELL_SIZE,
+        image::imageops::Triangle,
+    );
+    let mut canvas = image::RgbaImage::from_pixel(
+        EDITED_ICON_SIZE,
+        EDITED_ICON_SIZE,
+        image::Rgba([channel(red), channel(green), channel(blue), 255]),
+    );
+    let (left, top) = (
+        (EDITED_ICON_SIZE - glyph.width()) / 2,
+        (EDITED_ICON_SIZE - glyph.height()) / 2,
+    );
+    for (x, y, level) in glyph.enumerate_pixels() {
+        let coverage = ((level[0] as f32 / 255.0 - 0.35) / 0.15).clamp(0.0, 1.0);
+        if coverage == 0.0 {
+            continue;
+        }
+        let pixel = canvas.get_pixel_mut(left + x, top + y);
+        for (value, target) in pixel.0.iter_mut().zip(foreground.0).take(3) {
+            *value = (*value as f32 * (1.0 - coverage) + target as f32 * coverage).round() as u8;
+        }
+    }
+
+    render_icon(
+        &image::DynamicImage::ImageRgba8(canvas),
+        &IconStyle {
+            rounded: true,
+            ..Default::default()
+        },
+    )
 }
 
 lazy_static! {
@@ -166,6 +607,7 @@ lazy_static! {
     )
     .unwrap();
     static ref title_selctor: Selector = Selector::parse("title").unwrap();
+    static ref manifest_selector: Selector = Selector::parse("link[rel='manifest']").unwrap();
     static ref http: isahc::HttpClient = isahc::HttpClient::builder()
         .redirect_policy(config::RedirectPolicy::Limit(10))
         .build()
@@ -180,16 +622,81 @@ pub fn to_gdk_texture(buffer: &[u8], size: i32) -> gdk::Texture {
     gdk::Texture::for_pixbuf(&pixbuf)
 }
 
-async fn get_image_metadata(url: Url) -> anyhow::Result<Image> {
-    let mut response = http.get_async(url.to_string()).await?;
+async fn get_image_metadata(candidate: &IconCandidate) -> anyhow::Result<Image> {
+    let mut response = http.get_async(candidate.url.to_string()).await?;
+    if !response.status().is_success() {
+        bail!("Failed to fetch {}: {}", candidate.url, response.status());
+    }
+    let is_svg = candidate.is_svg
+        || response
+            .headers()
+            .get("content-type")
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.starts_with(SVG_MIME_TYPE));
     let buffer = response.bytes().await?;
-    let extension = url
-        .path_segments()
-        .and_then(|x| x.last())
-        .and_then(|x| Path::new(x).extension())
-        .and_then(|x| x.to_str());
 
-    Image::from_buffer(buffer, extension.is_some_and(|x| x == "svg"))
+    Image::from_buffer(buffer, is_svg)
+}
+
+/// Tries the candidates in order, returning the first that loads and fits
+async fn first_icon(candidates: &[IconCandidate]) -> Option<Image> {
+    for candidate in candidates {
+        if let Ok(image) = get_image_metadata(candidate)
+            .await
+            .and_then(Image::fit_to_max_size)
+        {
+            return Some(image);
+        }
+    }
+    None
+}
+
+/// Picks the best icon while downloading as few as possible
+/// SVGs win outright, then declared sizes decide which icon to try without downloading
+/// the rest, icons that declare nothing are all downloaded to compare them
+async fn best_icon(candidates: Vec<IconCandidate>) -> Option<Image> {
+    let mut urls = HashSet::new();
+    let candidates: Vec<IconCandidate> = candidates
+        .into_iter()
+        .filter(|x| urls.insert(x.url.clone()))
+        .collect();
+
+    let svgs: Vec<IconCandidate> = candidates.iter().filter(|x| x.is_svg).cloned().collect();
+    if let Some(image) = first_icon(&svgs).await {
+        return Some(image);
+    }
+
+    let (declared, undeclared): (Vec<IconCandidate>, Vec<IconCandidate>) = candidates
+        .into_iter()
+        .filter(|x| !x.is_svg)
+        .partition(|x| x.size.is_some());
+    // The largest icon that fits comes first, then the smallest of those that need scaling
+    let (mut fitting, mut oversized): (Vec<IconCandidate>, Vec<IconCandidate>) = declared
+        .into_iter()
+        .partition(|x| x.size.as_ref().is_some_and(|x| x.fits()));
+    fitting.sort_by(|a, b| b.size.cmp(&a.size));
+    oversized.sort_by(|a, b| a.size.cmp(&b.size));
+    let declared: Vec<IconCandidate> = fitting.into_iter().chain(oversized).collect();
+
+    let (undeclared, declared) = join(
+        join_all(undeclared.iter().map(get_image_metadata)),
+        first_icon(&declared),
+    )
+    .await;
+    undeclared
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.fit_to_max_size().ok())
+        .chain(declared)
+        .max()
+}
+
+/// Fetches a manifest, returning the url it was served from for resolving relative urls
+async fn get_manifest(url: Url) -> anyhow::Result<(WebManifest, Url)> {
+    let mut response = http.get_async(url.to_string()).await?;
+    let text = response.text().await?;
+    let url = Url::parse(response.effective_uri().unwrap().to_string().as_str())?;
+    Ok((serde_json::from_str(text.as_str())?, url))
 }
 
 pub async fn get_website_meta(url: Url) -> anyhow::Result<WebsiteMeta> {
@@ -197,46 +704,108 @@ pub async fn get_website_meta(url: Url) -> anyhow::Result<WebsiteMeta> {
     let html = req.text().await?;
     let url: Url = Url::parse(req.effective_uri().unwrap().to_string().as_str()).unwrap();
     let doc = Html::parse_document(html.as_str());
-    let mut paths = doc
-        .select(&icon_selector)
+
+    // A broken manifest shouldn't stop the rest of the page from being used
+    let manifest = match doc
+        .select(&manifest_selector)
         .filter_map(|elm| elm.attr("href"))
-        .collect::<HashSet<&str>>();
-    paths.insert("favicon.ico");
-    paths.insert("favicon.png");
-    paths.insert("/favicon.ico");
-    paths.insert("/favicon.png");
-    let paths = paths
-        .into_iter()
-        .filter_map(|path| url.join(path).ok())
-        .collect::<HashSet<Url>>();
-    let metadata = join_all(paths.into_iter().map(get_image_metadata)).await;
-    let best_image = metadata
-        .iter()
-        .filter_map(|x| x.as_ref().ok())
-        .filter(|x| {
-            if let ImageSize::Sized((w, _)) = x.size {
-                w <= 256
-            } else {
-                true
-            }
+        .find_map(|path| url.join(path).ok())
+    {
+        Some(manifest_url) => get_manifest(manifest_url).await.ok(),
+        None => None,
+    };
+    let (manifest, manifest_url) =
+        manifest.unwrap_or_else(|| (WebManifest::default(), url.clone()));
+
+    let mut candidates: Vec<IconCandidate> = doc
+        .select(&icon_selector)
+        .filter_map(|elm| {
+            Some(IconCandidate::new(
+                url.join(elm.attr("href")?).ok()?,
+                elm.attr("sizes"),
+                elm.attr("type"),
+            ))
         })
-        .fold(None, |acc: Option<&Image>, x| {
-            Some(match acc {
-                Some(a) => a.max(x),
-                None => x,
-            })
+        .collect();
+    candidates.extend(
+        manifest
+            .icons
+            .iter()
+            .filter(|x| x.is_usable())
+            .filter_map(|x| {
+                Some(IconCandidate::new(
+                    manifest_url.join(x.src.as_str()).ok()?,
+                    x.sizes.as_deref(),
+                    x.mime_type.as_deref(),
+                ))
+            }),
+    );
+    // Where browsers look when a page doesn't link to an icon
+    candidates.extend(
+        ["favicon.ico", "favicon.png", "/favicon.ico", "/favicon.png"]
+            .into_iter()
+            .filter_map(|path| Some(IconCandidate::new(url.join(path).ok()?, None, None))),
+    );
+    let icon = best_icon(candidates).await;
+    let nonblank = |x: &String| !x.trim().is_empty();
+    let title = manifest
+        .name
+        .filter(nonblank)
+        .or(manifest.short_name.filter(nonblank))
+        .or_else(|| {
+            doc.select(&title_selctor)
+                .map(|x| x.text())
+                .next()
+                .map(|x| x.collect::<String>())
         });
-    let title = doc
-        .select(&title_selctor)
-        .map(|x| x.text())
-        .next()
-        .map(|x| x.collect::<String>());
+    // Browsers ignore start urls and scopes that point to another origin
+    let same_origin = |path: Option<String>| {
+        path.and_then(|x| manifest_url.join(x.as_str()).ok())
+            .filter(|x| x.origin() == url.origin())
+    };
     Ok(WebsiteMeta {
-        icon: best_image.cloned(),
+        icon,
         title,
+        start_url: same_origin(manifest.start_url),
+        scope: same_origin(manifest.scope),
+        theme_color: manifest.theme_color,
+        background_color: manifest.background_color,
     })
 }
 
+/// Total size in bytes of the files under a directory
+/// Symlinks aren't followed and unreadable entries are skipped
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.path().symlink_metadata().ok()?;
+            Some(if metadata.is_dir() {
+                dir_size(&entry.path())
+            } else {
+                metadata.len()
+            })
+        })
+        .sum()
+}
+
+/// Parses a url, assuming https if the scheme was left out
+pub fn parse_url(url: &str) -> anyhow::Result<Url> {
+    if let Ok(url) = Url::parse(url) {
+        Ok(url)
+    } else {
+        let url = format!("https://{url}");
+        if let Ok(url) = Url::parse(url.as_str()) {
+            Ok(url)
+        } else {
+            bail!("Invalid url");
+        }
+    }
+}
+
 pub async fn icon_from_dialog(
     window: Option<&(impl IsA<gtk::Window> + Clone + 'static)>,
 ) -> anyhow::Result<gio::File> {
@@ -257,3 +826,6 @@ pub async fn icon_from_dialog(
 
     Ok(file)
 }
+
+#[cfg(test)]
+mod tests;
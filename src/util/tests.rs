@@ -0,0 +1,785 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use futures::executor::block_on;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+
+use super::*;
+
+const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 16"><rect width="16" height="16"/></svg>"#;
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let pixels = vec![255; (width * height * 4) as usize];
+    let mut buffer = Vec::new();
+    PngEncoder::new(&mut buffer)
+        .write_image(&pixels, width, height, image::ExtendedColorType::Rgba8)
+        .unwrap();
+    buffer
+}
+
+fn ico(width: u32, height: u32) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    image::DynamicImage::new_rgba8(width, height)
+        .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Ico)
+        .unwrap();
+    buffer
+}
+
+fn html(head: &str) -> String {
+    format!("<!DOCTYPE html><html><head>{head}</head><body></body></html>")
+}
+
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: "200 OK",
+            headers: vec![("Content-Type", content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    fn page(head: &str) -> Self {
+        Self::ok("text/html", html(head))
+    }
+
+    fn png(width: u32, height: u32) -> Self {
+        Self::ok("image/png", png(width, height))
+    }
+
+    fn redirect(location: &str) -> Self {
+        Self {
+            status: "301 Moved Permanently",
+            headers: vec![("Location", location.to_string())],
+            body: Vec::new(),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            status: "404 Not Found",
+            headers: vec![("Content-Type", "text/plain".to_string())],
+            body: b"Not found".to_vec(),
+        }
+    }
+}
+
+/// Serves fixed responses by path on a local port, anything else is a 404
+/// The routes are built with the server's url so pages can link to it absolutely
+fn serve(routes: impl FnOnce(&Url) -> Vec<(&'static str, Response)>) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let routes: Arc<HashMap<&'static str, Response>> =
+        Arc::new(routes(&base).into_iter().collect());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let routes = routes.clone();
+            std::thread::spawn(move || respond(stream, &routes));
+        }
+    });
+    base
+}
+
+fn respond(mut stream: TcpStream, routes: &HashMap<&'static str, Response>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Skip the headers, none of the fixtures look at them
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|x| x > 2) {
+        line.clear();
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .split('?')
+        .next()
+        .unwrap();
+    let not_found = Response::not_found();
+    let response = routes.get(path).unwrap_or(&not_found);
+
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(&response.body);
+}
+
+fn website_meta(url: Url) -> WebsiteMeta {
+    block_on(get_website_meta(url)).unwrap()
+}
+
+fn icon_size(meta: &WebsiteMeta) -> Option<ImageSize> {
+    meta.icon.as_ref().map(|x| x.size.clone())
+}
+
+#[test]
+fn variable_size_is_larger_than_any_fixed_size() {
+    assert!(ImageSize::Variable > ImageSize::Sized((256, 256)));
+    assert!(ImageSize::Sized((32, 32)) < ImageSize::Sized((64, 64)));
+    assert_eq!(
+        ImageSize::Sized((32, 32)).max(ImageSize::Variable),
+        ImageSize::Variable
+    );
+    assert_eq!(
+        ImageSize::Sized((32, 32)).min(ImageSize::Sized((16, 16))),
+        ImageSize::Sized((16, 16))
+    );
+}
+
+#[test]
+fn images_are_ordered_by_size() {
+    let small = Image::from_buffer(png(16, 16), false).unwrap();
+    let large = Image::from_buffer(png(48, 48), false).unwrap();
+    assert!(small < large);
+    assert_eq!(small.clone().max(large.clone()), large);
+}
+
+#[test]
+fn image_from_buffer_reads_size() {
+    let image = Image::from_buffer(png(24, 24), false).unwrap();
+    assert_eq!(image.size, ImageSize::Sized((24, 24)));
+}
+
+#[test]
+fn image_from_buffer_pads_non_square() {
+    let image = Image::from_buffer(png(32, 16), false).unwrap();
+    assert_eq!(image.size, ImageSize::Sized((32, 32)));
+    let padded = image::load_from_memory(&image.buffer).unwrap();
+    assert_eq!((padded.width(), padded.height()), (32, 32));
+    // The original is centered vertically, leaving transparent bands
+    let padded = padded.to_rgba8();
+    assert_eq!(padded.get_pixel(0, 0)[3], 0);
+    assert_eq!(padded.get_pixel(0, 16)[3], 255);
+}
+
+#[test]
+fn image_from_buffer_rejects_garbage() {
+    assert!(Image::from_buffer(b"Not found".to_vec(), false).is_err());
+}
+
+#[test]
+fn image_from_buffer_converts_to_png() {
+    let image = Image::from_buffer(ico(16, 16), false).unwrap();
+    assert_eq!(
+        image::guess_format(&image.buffer).unwrap(),
+        ImageFormat::Png
+    );
+}
+
+#[test]
+fn image_from_buffer_picks_largest_ico_frame() {
+    // The image crate on its own would pick the small frame for its color depth
+    let small = vec![255; 16 * 16 * 4];
+    let large = vec![255; 48 * 48 * 3];
+    let frames = [
+        IcoFrame::as_png(&small, 16, 16, image::ExtendedColorType::Rgba8).unwrap(),
+        IcoFrame::as_png(&large, 48, 48, image::ExtendedColorType::Rgb8).unwrap(),
+    ];
+    let mut buffer = Vec::new();
+    IcoEncoder::new(&mut buffer).encode_images(&frames).unwrap();
+
+    let image = Image::from_buffer(buffer, false).unwrap();
+    assert_eq!(image.size, ImageSize::Sized((48, 48)));
+}
+
+#[test]
+fn svg_has_variable_size() {
+    let image = Image::from_buffer(SVG.as_bytes().to_vec(), true).unwrap();
+    assert_eq!(image.size, ImageSize::Variable);
+}
+
+#[test]
+fn svg_must_be_svg() {
+    assert!(Image::from_buffer(html("").into_bytes(), true).is_err());
+}
+
+#[test]
+fn parse_sizes_prefers_largest_that_fits() {
+    assert_eq!(parse_sizes("32x32"), Some(ImageSize::Sized((32, 32))));
+    assert_eq!(
+        parse_sizes("16x16 48X48 32x32"),
+        Some(ImageSize::Sized((48, 48)))
+    );
+    assert_eq!(
+        parse_sizes("192x192 512x512"),
+        Some(ImageSize::Sized((192, 192)))
+    );
+    assert_eq!(
+        parse_sizes("1024x1024 512x512"),
+        Some(ImageSize::Sized((512, 512)))
+    );
+    assert_eq!(parse_sizes("any"), None);
+    assert_eq!(parse_sizes(""), None);
+}
+
+#[test]
+fn manifest_icon_purpose() {
+    let icon = |purpose: Option<&str>| ManifestIcon {
+        src: "icon.png".to_string(),
+        sizes: None,
+        mime_type: None,
+        purpose: purpose.map(|x| x.to_string()),
+    };
+    assert!(icon(None).is_usable());
+    assert!(icon(Some("any")).is_usable());
+    assert!(icon(Some("maskable any")).is_usable());
+    assert!(!icon(Some("maskable")).is_usable());
+    assert!(!icon(Some("monochrome")).is_usable());
+}
+
+#[test]
+fn parse_url_assumes_https() {
+    assert_eq!(
+        parse_url("example.com").unwrap().as_str(),
+        "https://example.com/"
+    );
+    assert_eq!(
+        parse_url("http://example.com/app").unwrap().as_str(),
+        "http://example.com/app"
+    );
+}
+
+#[test]
+fn relative_icon_href() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/app/",
+                Response::page(r#"<title>Relative</title><link rel="icon" href="icons/64.png">"#),
+            ),
+            ("/app/icons/64.png", Response::png(64, 64)),
+        ]
+    });
+    let meta = website_meta(base.join("app/").unwrap());
+    assert_eq!(meta.title.as_deref(), Some("Relative"));
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((64, 64))));
+}
+
+#[test]
+fn absolute_icon_href() {
+    let base = serve(|base| {
+        vec![
+            (
+                "/",
+                Response::page(&format!(
+                    r#"<title>Absolute</title><link rel="apple-touch-icon" href="{}">"#,
+                    base.join("static/touch.png").unwrap()
+                )),
+            ),
+            ("/static/touch.png", Response::png(180, 180)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(meta.title.as_deref(), Some("Absolute"));
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((180, 180))));
+}
+
+#[test]
+fn icons_resolve_against_redirected_url() {
+    let base = serve(|_| {
+        vec![
+            ("/", Response::redirect("/home/")),
+            (
+                "/home/",
+                Response::page(r#"<title>Redirected</title><link rel="icon" href="icon.png">"#),
+            ),
+            ("/home/icon.png", Response::png(32, 32)),
+            // Would be picked if the href was resolved against the original url
+            ("/icon.png", Response::png(128, 128)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(meta.title.as_deref(), Some("Redirected"));
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((32, 32))));
+}
+
+#[test]
+fn svg_icon_is_preferred() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" href="/icon.svg"><link rel="icon" href="/icon.png">"#,
+                ),
+            ),
+            ("/icon.svg", Response::ok("image/svg+xml", SVG)),
+            ("/icon.png", Response::png(128, 128)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Variable));
+}
+
+#[test]
+fn svg_type_attribute() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" type="image/svg+xml" href="/logo"><link rel="icon" sizes="64x64" href="/64.png">"#,
+                ),
+            ),
+            ("/logo", Response::ok("text/plain", SVG)),
+            ("/64.png", Response::png(64, 64)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Variable));
+}
+
+#[test]
+fn broken_svg_falls_back() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" href="/missing.svg"><link rel="icon" href="/icon.png">"#,
+                ),
+            ),
+            ("/icon.png", Response::png(64, 64)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((64, 64))));
+}
+
+#[test]
+fn declared_sizes_pick_the_icon() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" sizes="32x32" href="/32.png"><link rel="icon" sizes="64x64" href="/64.png"><link rel="icon" sizes="512x512" href="/512.png">"#,
+                ),
+            ),
+            // Both would win if they were downloaded
+            ("/32.png", Response::png(128, 128)),
+            ("/512.png", Response::png(256, 256)),
+            ("/64.png", Response::png(64, 64)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((64, 64))));
+}
+
+#[test]
+fn declared_icon_falls_back_when_broken() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" sizes="64x64" href="/64.png"><link rel="icon" sizes="32x32" href="/32.png">"#,
+                ),
+            ),
+            ("/32.png", Response::png(32, 32)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((32, 32))));
+}
+
+#[test]
+fn undeclared_icons_compete_with_declared() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" sizes="32x32" href="/32.png"><link rel="apple-touch-icon" href="/touch.png">"#,
+                ),
+            ),
+            ("/32.png", Response::png(32, 32)),
+            ("/touch.png", Response::png(180, 180)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((180, 180))));
+}
+
+#[test]
+fn ico_favicon() {
+    let base = serve(|_| {
+        vec![
+            ("/", Response::page("<title>Ico</title>")),
+            ("/favicon.ico", Response::ok("image/x-icon", ico(32, 32))),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((32, 32))));
+}
+
+#[test]
+fn non_square_icons_are_padded() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" href="/banner.png"><link rel="icon" href="/square.png">"#,
+                ),
+            ),
+            ("/banner.png", Response::png(128, 64)),
+            ("/square.png", Response::png(48, 48)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((128, 128))));
+}
+
+#[test]
+fn icons_above_256_are_not_kept_at_full_size() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" href="/512.png"><link rel="icon" href="/256.png"><link rel="icon" href="/16.png">"#,
+                ),
+            ),
+            ("/512.png", Response::png(512, 512)),
+            ("/256.png", Response::png(256, 256)),
+            ("/16.png", Response::png(16, 16)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((256, 256))));
+}
+
+#[test]
+fn large_declared_icon_is_scaled_down() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<link rel="icon" sizes="1024x1024" href="/1024.png"><link rel="icon" sizes="512x512" href="/512.png">"#,
+                ),
+            ),
+            ("/1024.png", Response::not_found()),
+            ("/512.png", Response::png(512, 512)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((256, 256))));
+    let icon = meta.icon.unwrap();
+    assert_eq!(
+        image::load_from_memory(&icon.buffer).unwrap().width(),
+        MAX_ICON_SIZE
+    );
+}
+
+#[test]
+fn large_undeclared_icon_is_scaled_down() {
+    let base = serve(|_| {
+        vec![
+            ("/", Response::page(r#"<link rel="icon" href="/512.png">"#)),
+            ("/512.png", Response::png(512, 512)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((256, 256))));
+}
+
+#[test]
+fn favicon_fallback() {
+    let base = serve(|_| {
+        vec![
+            ("/", Response::page("<title>Fallback</title>")),
+            ("/favicon.ico", Response::png(32, 32)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((32, 32))));
+}
+
+#[test]
+fn missing_favicons() {
+    let base = serve(|_| {
+        vec![(
+            "/",
+            Response::page(r#"<title>No Icons</title><link rel="icon" href="/missing.png">"#),
+        )]
+    });
+    let meta = website_meta(base);
+    assert_eq!(meta.title.as_deref(), Some("No Icons"));
+    assert!(meta.icon.is_none());
+}
+
+#[test]
+fn manifest_name_and_icons() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<title>Page Title</title><link rel="manifest" href="/static/app.webmanifest">"#,
+                ),
+            ),
+            (
+                "/static/app.webmanifest",
+                Response::ok(
+                    "application/manifest+json",
+                    r##"{
+                        "name": "Manifest Name",
+                        "start_url": "/start",
+                        "scope": "https://elsewhere.example/",
+                        "theme_color": "#336699",
+                        "icons": [
+                            { "src": "icon-96.png", "sizes": "96x96" },
+                            { "src": "maskable-192.png", "sizes": "192x192", "purpose": "maskable" }
+                        ]
+                    }"##,
+                ),
+            ),
+            ("/static/icon-96.png", Response::png(96, 96)),
+            ("/static/maskable-192.png", Response::png(192, 192)),
+        ]
+    });
+    let meta = website_meta(base.clone());
+    assert_eq!(meta.title.as_deref(), Some("Manifest Name"));
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((96, 96))));
+    assert_eq!(meta.start_url, Some(base.join("start").unwrap()));
+    // Scopes on another origin are ignored
+    assert_eq!(meta.scope, None);
+    assert_eq!(meta.theme_color.as_deref(), Some("#336699"));
+}
+
+#[test]
+fn blank_manifest_name_falls_back_to_short_name() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<title>Page Title</title><link rel="manifest" href="/manifest.json">"#,
+                ),
+            ),
+            (
+                "/manifest.json",
+                Response::ok(
+                    "application/manifest+json",
+                    r#"{ "name": "  ", "short_name": "Short" }"#,
+                ),
+            ),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(meta.title.as_deref(), Some("Short"));
+}
+
+#[test]
+fn broken_manifest_is_ignored() {
+    let base = serve(|_| {
+        vec![
+            (
+                "/",
+                Response::page(
+                    r#"<title>Page Title</title><link rel="manifest" href="/manifest.json"><link rel="icon" href="/icon.png">"#,
+                ),
+            ),
+            (
+                "/manifest.json",
+                Response::ok("application/json", "{ not json"),
+            ),
+            ("/icon.png", Response::png(64, 64)),
+        ]
+    });
+    let meta = website_meta(base);
+    assert_eq!(meta.title.as_deref(), Some("Page Title"));
+    assert_eq!(icon_size(&meta), Some(ImageSize::Sized((64, 64))));
+}
+
+fn opaque(width: u32, height: u32) -> image::DynamicImage {
+    image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+        width,
+        height,
+        image::Rgba([255, 255, 255, 255]),
+    ))
+}
+
+fn render(source: &image::DynamicImage, style: IconStyle) -> image::RgbaImage {
+    let buffer = render_icon(source, &style).unwrap();
+    assert_eq!(image::guess_format(&buffer).unwrap(), ImageFormat::Png);
+    image::load_from_memory(&buffer).unwrap().to_rgba8()
+}
+
+#[test]
+fn render_icon_pads_to_square() {
+    let icon = render(&opaque(32, 16), IconStyle::default());
+    assert_eq!(icon.dimensions(), (EDITED_ICON_SIZE, EDITED_ICON_SIZE));
+    assert_eq!(icon.get_pixel(0, 0)[3], 0);
+    assert_eq!(icon.get_pixel(128, 128)[3], 255);
+}
+
+#[test]
+fn render_icon_crops_to_square() {
+    let icon = render(
+        &opaque(32, 16),
+        IconStyle {
+            fit: IconFit::Crop,
+            ..Default::default()
+        },
+    );
+    assert_eq!(icon.dimensions(), (EDITED_ICON_SIZE, EDITED_ICON_SIZE));
+    assert_eq!(icon.get_pixel(0, 0)[3], 255);
+    assert_eq!(icon.get_pixel(255, 255)[3], 255);
+}
+
+#[test]
+fn render_icon_fills_background() {
+    let red = image::Rgba([255, 0, 0, 255]);
+    let icon = render(
+        &opaque(32, 16),
+        IconStyle {
+            background: Some(red),
+            ..Default::default()
+        },
+    );
+    assert_eq!(*icon.get_pixel(0, 0), red);
+    assert_eq!(*icon.get_pixel(128, 128), image::Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+fn render_icon_rounds_corners() {
+    let icon = render(
+        &opaque(16, 16),
+        IconStyle {
+            rounded: true,
+            ..Default::default()
+        },
+    );
+    for (x, y) in [(0, 0), (255, 0), (0, 255), (255, 255)] {
+        assert_eq!(icon.get_pixel(x, y)[3], 0);
+    }
+    // Edges between the corners stay untouched
+    assert_eq!(icon.get_pixel(128, 0)[3], 255);
+    assert_eq!(icon.get_pixel(0, 128)[3], 255);
+}
+
+#[test]
+fn render_icon_sharp_scaling_keeps_edges() {
+    let mut source = image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 0, 255]));
+    source.put_pixel(1, 0, image::Rgba([255, 255, 255, 255]));
+    source.put_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+    let source = image::DynamicImage::ImageRgba8(source);
+
+    let sharp = render(
+        &source,
+        IconStyle {
+            sharp: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(sharp.get_pixel(127, 64)[0], 0);
+    assert_eq!(sharp.get_pixel(128, 64)[0], 255);
+
+    let smooth = render(&source, IconStyle::default());
+    assert_ne!(smooth.get_pixel(127, 64)[0], 0);
+}
+
+#[test]
+fn decode_icon_reads_ico() {
+    let image = decode_icon(&ico(48, 48), false).unwrap();
+    assert_eq!((image.width(), image.height()), (48, 48));
+}
+
+#[test]
+fn decode_icon_rejects_garbage() {
+    assert!(decode_icon(b"not an image", false).is_err());
+    assert!(decode_icon(b"not an image", true).is_err());
+}
+
+#[test]
+fn icon_letter_from_title() {
+    let url = Url::parse("https://www.example.com").unwrap();
+    assert_eq!(icon_letter("spider", &url), 'S');
+    // Leading symbols are skipped
+    assert_eq!(icon_letter("  #1 News", &url), '1');
+}
+
+#[test]
+fn icon_letter_falls_back_to_domain() {
+    let url = Url::parse("https://www.example.com").unwrap();
+    assert_eq!(icon_letter("", &url), 'E');
+    assert_eq!(icon_letter("—", &url), 'E');
+    let url = Url::parse("http://127.0.0.1:8080").unwrap();
+    assert_eq!(icon_letter("", &url), '1');
+}
+
+#[test]
+fn icon_color_depends_on_domain() {
+    let url = Url::parse("https://example.com/a").unwrap();
+    let color = icon_color(&url);
+    assert!(LETTER_ICON_COLORS.contains(&color));
+    assert_eq!(
+        icon_color(&Url::parse("https://example.com/b").unwrap()),
+        color
+    );
+}
+
+#[test]
+fn icon_letter_skips_letters_without_glyph() {
+    let url = Url::parse("https://www.example.com").unwrap();
+    assert_eq!(icon_letter("Ärzte", &url), 'E');
+    let url = Url::parse("file:///tmp").unwrap();
+    assert_eq!(icon_letter("Ärzte", &url), '?');
+}
+
+#[test]
+fn letter_icon_draws_letter_on_theme_color() {
+    let url = Url::parse("https://example.com").unwrap();
+    let buffer = letter_icon("Spider", &url, Some("#336699")).unwrap();
+    let icon = image::load_from_memory_with_format(&buffer, ImageFormat::Png)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(icon.dimensions(), (EDITED_ICON_SIZE, EDITED_ICON_SIZE));
+    assert_eq!(
+        *icon.get_pixel(128, 8),
+        image::Rgba([0x33, 0x66, 0x99, 255])
+    );
+    // The middle stroke of the S, white on a dark background
+    assert_eq!(*icon.get_pixel(128, 128), image::Rgba([255, 255, 255, 255]));
+    assert_eq!(icon.get_pixel(0, 0)[3], 0);
+}
+
+#[test]
+fn letter_icon_falls_back_to_domain_color() {
+    let url = Url::parse("https://example.com").unwrap();
+    let buffer = letter_icon("", &url, Some("not a color")).unwrap();
+    let icon = image::load_from_memory(&buffer).unwrap().to_rgba8();
+    let color = gdk::RGBA::parse(icon_color(&url)).unwrap();
+    let channel = |x: f32| (x * 255.0).round() as u8;
+    assert_eq!(
+        *icon.get_pixel(128, 8),
+        image::Rgba([
+            channel(color.red()),
+            channel(color.green()),
+            channel(color.blue()),
+            255
+        ])
+    );
+}
@@ -25,10 +25,16 @@ use ashpd::WindowIdentifier;
 use glib::clone;
 use gtk::{gio, glib};
 
+use std::path::PathBuf;
+
 use crate::app_page::AppPage;
 use crate::app_row::AppRow;
 use crate::application::settings;
-use crate::apps::{copy_app_dir, get_app_details, get_app_icon, install_app, uninstall_app};
+use crate::apps::{
+    copy_app_dir, get_app_details, get_app_icon, install_app, is_app_running, trim_app_cache,
+    uninstall_app, update_autostart, AppDetails,
+};
+use crate::bundle;
 use crate::create_app_dialog::{gen_unique_id, CreateAppDialog, APP_ID_LENGTH};
 use crate::home_page::HomePage;
 
@@ -141,6 +147,24 @@ impl SpiderWindow {
                     );
                 })
                 .build(),
+            gio::ActionEntry::builder("duplicate")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(move |win: &Self, _, id| {
+                    let id = id
+                        .expect("no id provided")
+                        .get::<String>()
+                        .expect("invalid id type provided");
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        win,
+                        async move {
+                            if let Err(err) = win.duplicate_app(id).await {
+                                win.toast(err.to_string().as_str());
+                            }
+                        }
+                    ));
+                })
+                .build(),
             gio::ActionEntry::builder("reinstall")
                 .parameter_type(Some(&String::static_variant_type()))
                 .activate(move |win: &Self, _, id| {
@@ -151,6 +175,59 @@ impl SpiderWindow {
                     );
                 })
                 .build(),
+            gio::ActionEntry::builder("export")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(move |win: &Self, _, id| {
+                    win.confirm_export_apps(vec![id
+                        .expect("no id provided")
+                        .get::<String>()
+                        .expect("invalid id type provided")]);
+                })
+                .build(),
+            gio::ActionEntry::builder("export-all")
+                .activate(move |win: &Self, _, _| {
+                    win.confirm_export_apps(settings().get::<Vec<String>>("app-ids"));
+                })
+                .build(),
+            gio::ActionEntry::builder("trim-cache")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(move |win: &Self, _, id| {
+                    let id = id
+                        .expect("no id provided")
+                        .get::<String>()
+                        .expect("invalid id type provided");
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        win,
+                        async move {
+                            win.trim_caches(vec![id]).await;
+                        }
+                    ));
+                })
+                .build(),
+            gio::ActionEntry::builder("trim-all-caches")
+                .activate(move |win: &Self, _, _| {
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        win,
+                        async move {
+                            win.trim_caches(settings().get::<Vec<String>>("app-ids"))
+                                .await;
+                        }
+                    ));
+                })
+                .build(),
+            gio::ActionEntry::builder("import")
+                .activate(move |win: &Self, _, _| {
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        win,
+                        async move {
+                            win.import_apps().await;
+                        }
+                    ));
+                })
+                .build(),
             gio::ActionEntry::builder("notify")
                 .parameter_type(Some(&String::static_variant_type()))
                 .activate(move |win: &Self, _, msg| {
@@ -199,6 +276,13 @@ impl SpiderWindow {
         Ok(())
     }
 
+    async fn duplicate_app(&self, id: String) -> anyhow::Result<()> {
+        let details = get_app_details(&id).ok_or(anyhow!("failed to get app details"))?;
+        let icon = get_app_icon(&id).await?;
+        CreateAppDialog::duplicate(&details, icon).present(Some(self));
+        Ok(())
+    }
+
     async fn delete_app(&self, id: String) -> anyhow::Result<()> {
         uninstall_app(id.as_str()).await?;
         self.refresh();
@@ -273,6 +357,155 @@ impl SpiderWindow {
         );
     }
 
+    fn confirm_export_apps(&self, ids: Vec<String>) {
+        let include_data = gtk::CheckButton::with_label("Include login data");
+        let confirm_dialog = adw::AlertDialog::new(
+            Some("Export apps?"),
+            Some("Login data contains cookies and tokens, only share it with people you trust."),
+        );
+        confirm_dialog.set_extra_child(Some(&include_data));
+        confirm_dialog.add_responses(&[("cancel", "Cancel"), ("export", "Export")]);
+        confirm_dialog.set_response_appearance("export", adw::ResponseAppearance::Suggested);
+        confirm_dialog.present(Some(self));
+        confirm_dialog.connect_response(
+            Some("export"),
+            clone!(
+                #[strong(rename_to=_self)]
+                self,
+                move |_, _| {
+                    glib::spawn_future_local(clone!(
+                        #[strong]
+                        _self,
+                        #[strong]
+                        ids,
+                        #[strong]
+                        include_data,
+                        async move {
+                            _self.export_apps(ids, include_data.is_active()).await;
+                        }
+                    ));
+                }
+            ),
+        );
+    }
+
+    async fn trim_caches(&self, ids: Vec<String>) {
+        let mut result = Ok(());
+        let mut skipped = 0;
+        for id in &ids {
+            // Trimming everything leaves open apps alone rather than failing on them
+            if ids.len() > 1 && is_app_running(id).await {
+                skipped += 1;
+                continue;
+            }
+            result = result.and(trim_app_cache(id).await);
+        }
+        self.update_sizes();
+
+        let message = match result {
+            Ok(_) if ids.len() == 1 => "Successfully trimmed cache!".to_string(),
+            Ok(_) if skipped == 1 => "Trimmed all caches, 1 open app was skipped".to_string(),
+            Ok(_) if skipped > 1 => format!("Trimmed all caches, {skipped} open apps were skipped"),
+            Ok(_) => "Successfully trimmed all caches!".to_string(),
+            Err(err) => err.to_string(),
+        };
+        self.toast(message.as_str());
+    }
+
+    /// Measures the disk usage shown in the sidebar and the open page again
+    fn update_sizes(&self) {
+        let imp = self.imp();
+        let mut child = imp.apps_listbox.first_child();
+        while let Some(row) = child {
+            if let Some(row) = row.downcast_ref::<AppRow>() {
+                row.update_size();
+            }
+            child = row.next_sibling();
+        }
+        if let Some(page) = imp.split_view.content().and_downcast::<AppPage>() {
+            page.update_storage();
+        }
+    }
+
+    async fn export_apps(&self, ids: Vec<String>, include_data: bool) {
+        let Some(path) = gtk::FileDialog::builder()
+            .accept_label("Export")
+            .modal(true)
+            .title("Export Apps")
+            .initial_name("spider-apps.tar.gz")
+            .build()
+            .save_future(Some(self))
+            .await
+            .ok()
+            .and_then(|x| x.path())
+        else {
+            return;
+        };
+
+        let message = match bundle::export_apps(&ids, path, include_data).await {
+            Ok(_) => "Successfully exported apps!".to_string(),
+            Err(err) => err.to_string(),
+        };
+        self.toast(message.as_str());
+    }
+
+    async fn import_apps(&self) {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Spider Bundles"));
+        filter.add_suffix("tar.gz");
+
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let Some(path) = gtk::FileDialog::builder()
+            .accept_label("Import")
+            .modal(true)
+            .title("Import Apps")
+            .filters(&filters)
+            .build()
+            .open_future(Some(self))
+            .await
+            .ok()
+            .and_then(|x| x.path())
+        else {
+            return;
+        };
+
+        let message = match self.install_bundle(path).await {
+            Ok(count) => format!("Successfully imported {count} apps!"),
+            Err(err) => err.to_string(),
+        };
+        self.toast(message.as_str());
+    }
+
+    async fn install_bundle(&self, path: PathBuf) -> anyhow::Result<usize> {
+        let bundle = bundle::import_bundle(path).await?;
+        let mut autostart = false;
+        for app in &bundle.apps {
+            // Imported apps always get new ids so they can't clash with existing ones
+            let details = AppDetails {
+                id: gen_unique_id(),
+                ..app.details.clone()
+            };
+            app.restore_data(&details.id)?;
+            install_app(
+                &details,
+                app.icon.clone(),
+                &WindowIdentifier::from_native(&self.root().unwrap()).await,
+            )
+            .await?;
+            autostart |= details.autostart;
+            self.refresh();
+        }
+        if autostart {
+            update_autostart(Some(
+                WindowIdentifier::from_native(&self.root().unwrap()).await,
+            ))
+            .await?;
+        }
+        Ok(bundle.apps.len())
+    }
+
     fn refresh(&self) {
         let imp = self.imp();
         let selected_id = self.selected_page_id();